@@ -151,6 +151,52 @@ def field_rust_type(field, types_map):
     # Default: use first type
     return tg_to_rust(types[0], not required, types_map)
 
+def is_attachable_field(type_name, field):
+    """String fields of Input* types that accept "attach://<file_attach_name>".
+
+    These are typed as InputFileOrString so in-memory files can be uploaded
+    from inside them; the files are collected at request time by
+    to_value_collecting in tgbotrs/src/input_file.rs.
+    """
+    return (
+        type_name.startswith('Input')
+        and field['types'] == ['String']
+        and 'attach://' in field['description']
+    )
+
+def type_field_rust_type(type_name, field, types_map):
+    """Like field_rust_type, but for a field of the struct `type_name`."""
+    if is_attachable_field(type_name, field):
+        return 'InputFileOrString' if field['required'] else 'Option<InputFileOrString>'
+    return field_rust_type(field, types_map)
+
+def strip_arrays(t):
+    while is_array(t):
+        t = strip_array(t)
+    return t
+
+def file_carrying_types(types_map):
+    """Names of all types that can hold an InputFile somewhere inside them."""
+    carrying = {'InputFile'}
+    changed = True
+    while changed:
+        changed = False
+        for name, tg in types_map.items():
+            if name in carrying:
+                continue
+            refs = list(tg.get('subtypes', []))
+            for field in tg.get('fields', []):
+                refs.extend(strip_arrays(t) for t in field['types'])
+            if any(r in carrying for r in refs) or any(
+                is_attachable_field(name, f) for f in tg.get('fields', [])
+            ):
+                carrying.add(name)
+                changed = True
+    return carrying
+
+def field_carries_files(field, carrying):
+    return any(strip_arrays(t) in carrying for t in field['types'])
+
 def opt_wrap(rust_type, optional):
     """Ensure a type is wrapped in Option if optional."""
     if optional and not rust_type.startswith('Option<'):
@@ -232,7 +278,7 @@ def generate_types(spec):
             #   (a) every field is optional (Option<...>), so ..Default::default() works, OR
             #   (b) the type is in FORCE_DEFAULT (explicitly allowlisted in this file)
             all_fields_optional = all(
-                field_rust_type(field, types_map).startswith('Option<')
+                type_field_rust_type(type_name, field, types_map).startswith('Option<')
                 for field in fields
             ) if fields else False
             want_default = all_fields_optional or type_name in FORCE_DEFAULT
@@ -244,7 +290,7 @@ def generate_types(spec):
             for field in fields:
                 fname = safe_field_name(field['name'])
                 fdesc = field['description'].replace('\n', ' ')
-                ftype = field_rust_type(field, types_map)
+                ftype = type_field_rust_type(type_name, field, types_map)
                 lines.append(f'    /// {fdesc}')
                 # serde rename if the field name differs or is a keyword
                 if fname != field['name']:
@@ -286,6 +332,8 @@ def generate_methods(spec):
     lines.append(f'use crate::{{Bot, BotError, ChatId, InputFile, InputFileOrString, ReplyMarkup, InputMedia}};')
     lines.append(f'')
 
+    carrying = file_carrying_types(types_map)
+
     for method_name in sorted(methods_map.keys()):
         method = methods_map[method_name]
         fn_name = method_fn_name(method_name)
@@ -339,12 +387,10 @@ def generate_methods(spec):
         # Return type
         ret = return_rust_type(returns, types_map)
 
-        # Identify file-upload methods: those with an InputFileOrString required field
-        file_field = None
-        for field in required_fields:
-            if field_rust_type(field, types_map) == 'InputFileOrString':
-                file_field = field['name']
-                break
+        # Methods with a field that can hold an InputFile collect every
+        # in-memory file while serializing the request, replacing it with a
+        # unique attach:// name, and go through call_api_with_attachments.
+        has_attachments = any(field_carries_files(f, carrying) for f in all_fields)
 
         # Signature args
         sig_parts = []
@@ -359,8 +405,11 @@ def generate_methods(spec):
             elif ftype == 'InputFileOrString':
                 sig_parts.append(f'{fname}: impl Into<InputFileOrString>')
             elif ftype == 'InputMedia':
-                # sendMediaGroup needs Vec<InputMedia> - always a list
-                sig_parts.append(f'{fname}: Vec<InputMedia>')
+                # sendMediaGroup takes a list; editMessageMedia a single item
+                if is_array(field['types'][0]):
+                    sig_parts.append(f'{fname}: Vec<InputMedia>')
+                else:
+                    sig_parts.append(f'{fname}: InputMedia')
             else:
                 sig_parts.append(f'{fname}: {ftype}')
 
@@ -378,40 +427,33 @@ def generate_methods(spec):
 
         # Build body
         lines.append(f'        let mut req = serde_json::Map::new();')
+        if has_attachments:
+            lines.append(f'        let mut files = Vec::new();')
         for field in required_fields:
             fname = safe_field_name(field['name'])
             ftype = field_rust_type(field, types_map)
             if ftype == 'InputFileOrString':
-                pass  # skipped here; added via call_api_with_file below
-            elif ftype == 'InputMedia':
-                # sendMediaGroup: Vec<InputMedia> - serialize as JSON array
-                lines.append(f'        req.insert("{field["name"]}".into(), serde_json::to_value(&{fname}).unwrap_or_default());')
+                lines.append(f'        req.insert("{field["name"]}".into(), crate::input_file::to_value_collecting::<InputFileOrString>(&{fname}.into(), &mut files)?);')
+            elif field_carries_files(field, carrying):
+                # Memory files inside the value are moved into `files`
+                lines.append(f'        req.insert("{field["name"]}".into(), crate::input_file::to_value_collecting(&{fname}, &mut files)?);')
             else:
                 expr = f'{fname}.into()' if ftype in ('String', 'ChatId') else fname
                 lines.append(f'        req.insert("{field["name"]}".into(), serde_json::to_value({expr}).unwrap_or_default());')
 
         if has_opts:
             lines.append(f'        if let Some(p) = params {{')
-            lines.append(f'            let extra = serde_json::to_value(&p).unwrap_or_default();')
+            if has_attachments:
+                lines.append(f'            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;')
+            else:
+                lines.append(f'            let extra = serde_json::to_value(&p).unwrap_or_default();')
             lines.append(f'            if let serde_json::Value::Object(m) = extra {{')
             lines.append(f'                for (k, v) in m {{ if !v.is_null() {{ req.insert(k, v); }} }}')
             lines.append(f'            }}')
             lines.append(f'        }}')
 
-        if file_field:
-            fn_arg = safe_field_name(file_field)
-            single_line = f'        self.call_api_with_file("{method_name}", req, "{file_field}", {fn_arg}.into())'
-            if len(single_line) > 100:
-                lines.append(f'        self.call_api_with_file(')
-                lines.append(f'            "{method_name}",')
-                lines.append(f'            req,')
-                lines.append(f'            "{file_field}",')
-                lines.append(f'            {fn_arg}.into(),')
-                lines.append(f'        )')
-                lines.append(f'        .await')
-            else:
-                lines.append(f'        self.call_api_with_file("{method_name}", req, "{file_field}", {fn_arg}.into())')
-                lines.append(f'            .await')
+        if has_attachments:
+            lines.append(f'        self.call_api_with_attachments("{method_name}", req, files).await')
         else:
             lines.append(f'        self.call_api("{method_name}", serde_json::Value::Object(req)).await')
         lines.append(f'    }}')
@@ -1,31 +1,18 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 
 use serde::Deserialize;
 
 use crate::{
-    client::{BotClient, FormPart, ReqwestClient},
-    input_file::{InputFile, InputFileOrString},
+    client::{BotClient, FormBody, FormPart, ReqwestClient},
+    error::mask_token,
+    input_file::InputFileOrString,
+    layer::BotClientLayer,
+    rate_limit::RateLimit,
+    retry::RetryPolicy,
     types::User,
     BotError,
 };
 
-fn infer_mime(filename: &str) -> String {
-    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
-    match ext.as_str() {
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "gif" => "image/gif",
-        "webp" => "image/webp",
-        "mp4" => "video/mp4",
-        "mp3" => "audio/mpeg",
-        "ogg" => "audio/ogg",
-        "pdf" => "application/pdf",
-        "webm" => "video/webm",
-        _ => "application/octet-stream",
-    }
-    .to_string()
-}
-
 const DEFAULT_API_URL: &str = "https://api.telegram.org";
 
 /// The main Bot struct. Create one per bot token.
@@ -39,8 +26,9 @@ const DEFAULT_API_URL: &str = "https://api.telegram.org";
 /// println!("Running as @{}", bot.me.username.as_deref().unwrap_or(""));
 /// # }
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Bot {
+    /// Bot token from @BotFather. Masked in `Debug` output.
     pub token: String,
     /// Bot info populated via `getMe` on creation.
     pub me: User,
@@ -50,6 +38,26 @@ pub struct Bot {
     pub(crate) base: String,
     /// Pluggable HTTP back-end. Defaults to [`ReqwestClient`].
     pub(crate) client: Arc<dyn BotClient>,
+    /// Retry policy for API calls; `None` means a single attempt.
+    pub(crate) retry: Option<RetryPolicy>,
+    /// Set when calls to migrated groups are resent to the new supergroup.
+    pub(crate) migration: Option<Arc<MigrationHook>>,
+}
+
+/// Called with `(old_chat_id, new_chat_id)` when a group became a supergroup.
+type MigrationHook = dyn Fn(i64, i64) + Send + Sync;
+
+impl fmt::Debug for Bot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bot")
+            .field("token", &mask_token(&self.token))
+            .field("me", &self.me)
+            .field("api_url", &self.api_url)
+            .field("client", &self.client)
+            .field("retry", &self.retry)
+            .field("auto_migration", &self.migration.is_some())
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -67,6 +75,16 @@ struct ResponseParameters {
     retry_after: Option<i64>,
 }
 
+/// The supergroup a failed call should be resent to, if any.
+fn migrated_to(err: &BotError) -> Option<i64> {
+    match err {
+        BotError::Api {
+            migrate_to_chat_id, ..
+        } => *migrate_to_chat_id,
+        _ => None,
+    }
+}
+
 fn parse_bot_id(token: &str) -> Result<i64, BotError> {
     token
         .split(':')
@@ -75,6 +93,20 @@ fn parse_bot_id(token: &str) -> Result<i64, BotError> {
         .ok_or(BotError::InvalidToken)
 }
 
+/// Turn a JSON request body into text form parts, skipping nulls.
+fn body_to_parts(body: serde_json::Map<String, serde_json::Value>) -> Vec<FormPart> {
+    body.into_iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| {
+            let text = match &v {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            FormPart::text(k, text)
+        })
+        .collect()
+}
+
 fn stub_user(id: i64) -> User {
     User {
         id,
@@ -135,6 +167,8 @@ impl Bot {
             api_url,
             base,
             client: Arc::new(client),
+            retry: None,
+            migration: None,
         };
 
         bot.me = bot.call_api("getMe", serde_json::json!({})).await?;
@@ -164,6 +198,8 @@ impl Bot {
             api_url,
             base,
             client: Arc::new(client),
+            retry: None,
+            migration: None,
         })
     }
 
@@ -184,9 +220,61 @@ impl Bot {
             api_url,
             base,
             client: Arc::new(client),
+            retry: None,
+            migration: None,
         })
     }
 
+    /// Retry failed API calls according to `policy`.
+    ///
+    /// See [`RetryPolicy`] for which failures are retried.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Throttle outgoing messages to stay within Telegram's limits.
+    ///
+    /// Wraps the current client in a
+    /// [`RateLimitedClient`](crate::RateLimitedClient); clones of the returned
+    /// bot share one set of limits.
+    pub fn with_rate_limit(self, limit: RateLimit) -> Self {
+        self.with_layer(limit)
+    }
+
+    /// Wrap the HTTP client in `layer`. Layers added later run first.
+    pub fn with_layer(mut self, layer: impl BotClientLayer) -> Self {
+        self.client = layer.layer(self.client);
+        self
+    }
+
+    /// Wrap the HTTP client in several layers. The first layer is the
+    /// outermost, so it sees each request first and each response last.
+    pub fn with_layers<L: BotClientLayer>(mut self, layers: impl IntoIterator<Item = L>) -> Self {
+        let layers: Vec<L> = layers.into_iter().collect();
+        for layer in layers.iter().rev() {
+            self.client = layer.layer(self.client);
+        }
+        self
+    }
+
+    /// Resend calls that fail because the target group was upgraded to a
+    /// supergroup, using the new chat ID from `migrate_to_chat_id`.
+    pub fn with_auto_migration(mut self) -> Self {
+        if self.migration.is_none() {
+            self.migration = Some(Arc::new(|_, _| {}));
+        }
+        self
+    }
+
+    /// Like [`Bot::with_auto_migration`], and also call
+    /// `hook(old_chat_id, new_chat_id)` before each resend so the application
+    /// can update stored chat IDs.
+    pub fn on_chat_migrated(mut self, hook: impl Fn(i64, i64) + Send + Sync + 'static) -> Self {
+        self.migration = Some(Arc::new(hook));
+        self
+    }
+
     // API plumbing
     /// Build the full endpoint URL for a Telegram method name.
     pub fn endpoint(&self, method: &str) -> String {
@@ -200,13 +288,51 @@ impl Bot {
         T: for<'de> Deserialize<'de>,
     {
         let url = self.endpoint(method);
-        let bytes = self.client.post_json(&url, body).await?;
+        let Some(hook) = &self.migration else {
+            return self.send_json(method, &url, body).await;
+        };
+        let err = match self.send_json(method, &url, body.clone()).await {
+            Err(e) => e,
+            ok => return ok,
+        };
+        let old_id = body.get("chat_id").and_then(serde_json::Value::as_i64);
+        let (Some(old_id), Some(new_id)) = (old_id, migrated_to(&err)) else {
+            return Err(err);
+        };
+        hook(old_id, new_id);
+        let mut body = body;
+        body["chat_id"] = new_id.into();
+        self.send_json(method, &url, body).await
+    }
+
+    async fn send_json<T>(
+        &self,
+        method: &str,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<T, BotError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let Some(policy) = &self.retry else {
+            return self.post_json_once(url, body).await;
+        };
+        policy
+            .run(method, || self.post_json_once(url, body.clone()))
+            .await
+    }
+
+    async fn post_json_once<T>(&self, url: &str, body: serde_json::Value) -> Result<T, BotError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let bytes = self.client.post_json(url, body).await?;
         let tg: TelegramResponse<T> = serde_json::from_slice(&bytes)?;
         self.unwrap_response(tg)
     }
 
-    /// Make an API call using multipart when a `Memory` file is present,
-    /// JSON otherwise.
+    /// Make an API call using multipart when `file` needs uploading (`Memory`,
+    /// `Path` or `Stream`), JSON otherwise.
     pub async fn call_api_with_file<T>(
         &self,
         method: &str,
@@ -217,36 +343,51 @@ impl Bot {
     where
         T: for<'de> Deserialize<'de>,
     {
-        match file {
-            InputFileOrString::File(InputFile::Memory { filename, data }) => {
-                let mime = infer_mime(&filename);
-
-                let mut parts: Vec<FormPart> = body
-                    .into_iter()
-                    .filter(|(_, v)| !v.is_null())
-                    .map(|(k, v)| {
-                        let text = match &v {
-                            serde_json::Value::String(s) => s.clone(),
-                            other => other.to_string(),
-                        };
-                        FormPart::text(k, text)
-                    })
-                    .collect();
-
-                parts.push(FormPart::bytes(file_field, filename, mime, data));
+        let upload = match &file {
+            InputFileOrString::File(f) => f.to_form_body()?,
+            InputFileOrString::String(_) => None,
+        };
+        match upload {
+            Some(upload) => {
+                let mut parts = body_to_parts(body);
+                parts.push(FormPart {
+                    name: file_field.into(),
+                    body: upload,
+                });
                 self.call_api_multipart(method, parts).await
             }
-            other => {
+            None => {
                 let mut req = body;
                 req.insert(
                     file_field.into(),
-                    serde_json::to_value(other).unwrap_or_default(),
+                    serde_json::to_value(file).unwrap_or_default(),
                 );
                 self.call_api(method, serde_json::Value::Object(req)).await
             }
         }
     }
 
+    /// Make an API call with files collected from the request body.
+    ///
+    /// `attachments` are the parts referenced via `attach://` somewhere in
+    /// `body`. Sends multipart when there are any, JSON otherwise.
+    pub async fn call_api_with_attachments<T>(
+        &self,
+        method: &str,
+        body: serde_json::Map<String, serde_json::Value>,
+        attachments: Vec<FormPart>,
+    ) -> Result<T, BotError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        if attachments.is_empty() {
+            return self.call_api(method, serde_json::Value::Object(body)).await;
+        }
+        let mut parts = body_to_parts(body);
+        parts.extend(attachments);
+        self.call_api_multipart(method, parts).await
+    }
+
     /// Make a `multipart/form-data` API call directly from [`FormPart`]s.
     pub async fn call_api_multipart<T>(
         &self,
@@ -257,7 +398,64 @@ impl Bot {
         T: for<'de> Deserialize<'de>,
     {
         let url = self.endpoint(method);
-        let bytes = self.client.post_form(&url, parts).await?;
+        let Some(hook) = &self.migration else {
+            return self.send_form(method, &url, parts).await;
+        };
+        // Streamed parts can't be sent twice, so such requests are never resent.
+        let backup: Option<Vec<FormPart>> = parts.iter().map(FormPart::try_clone).collect();
+        let err = match self.send_form(method, &url, parts).await {
+            Err(e) => e,
+            ok => return ok,
+        };
+        let (Some(mut parts), Some(new_id)) = (backup, migrated_to(&err)) else {
+            return Err(err);
+        };
+        let Some(chat_id) = parts.iter_mut().find(|p| p.name == "chat_id") else {
+            return Err(err);
+        };
+        let old_id = match &chat_id.body {
+            FormBody::Text(id) => id.parse().ok(),
+            _ => None,
+        };
+        let Some(old_id) = old_id else {
+            return Err(err);
+        };
+        hook(old_id, new_id);
+        chat_id.body = FormBody::Text(new_id.to_string());
+        self.send_form(method, &url, parts).await
+    }
+
+    async fn send_form<T>(
+        &self,
+        method: &str,
+        url: &str,
+        parts: Vec<FormPart>,
+    ) -> Result<T, BotError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        // Streamed parts can't be sent twice, so such requests are never retried.
+        let policy = self
+            .retry
+            .as_ref()
+            .filter(|_| parts.iter().all(|p| p.try_clone().is_some()));
+        let Some(policy) = policy else {
+            return self.post_form_once(url, parts).await;
+        };
+        // `FormPart` isn't `Sync`, so the closure owns the template parts.
+        policy
+            .run(method, move || {
+                let parts = parts.iter().filter_map(FormPart::try_clone).collect();
+                self.post_form_once(url, parts)
+            })
+            .await
+    }
+
+    async fn post_form_once<T>(&self, url: &str, parts: Vec<FormPart>) -> Result<T, BotError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let bytes = self.client.post_form(url, parts).await?;
         let tg: TelegramResponse<T> = serde_json::from_slice(&bytes)?;
         self.unwrap_response(tg)
     }
@@ -14,17 +14,33 @@
 //! let bot_a = Bot::new("TOKEN_A").await?;
 //! let bot_b = Bot::new("TOKEN_B").await?;
 //!
-//! let mut map = BotMapping::new();
-//! map.add_bot("/bot_a", bot_a, Dispatcher::new(DispatcherOpts::default()));
-//! map.add_bot("/bot_b", bot_b, Dispatcher::new(DispatcherOpts::default()));
+//! // Register each bot's webhook at https://bots.example.com/{path} on startup.
+//! let map = BotMapping::new().webhook_base_url("https://bots.example.com");
+//! map.add_bot_with_secret("/bot_a", bot_a, Dispatcher::new(DispatcherOpts::default()), "secret_a");
+//! map.add_bot_with_secret("/bot_b", bot_b, Dispatcher::new(DispatcherOpts::default()), "secret_b");
 //!
 //! // Serve on port 8080.
 //! map.serve(8080).await?;
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! Requests whose `X-Telegram-Bot-Api-Secret-Token` header doesn't match the
+//! bot's secret are rejected with `403 Forbidden`.
+//!
+//! Clones of a `BotMapping` share its bots, so bots can be connected and
+//! disconnected at runtime with [`BotMapping::register_bot`] and
+//! [`BotMapping::unregister_bot`] while the server is running.
+//!
+//! To serve the bots from an existing axum application instead, call
+//! [`BotMapping::register_webhooks`] and mount [`BotMapping::router`], e.g.
+//! `Router::new().nest("/tg", map.router())`.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
 
 use axum::{
     body::Bytes,
@@ -33,19 +49,33 @@ use axum::{
     routing::post,
     Router,
 };
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
-use crate::{framework::Dispatcher, types::Update, Bot, BotError};
+use crate::{
+    error::redact_token, framework::Dispatcher, types::Update, webhook::secret_token_valid, Bot,
+    BotError,
+};
 
 struct Entry {
     bot: Bot,
     dispatcher: Arc<Dispatcher>,
+    secret_token: Option<String>,
 }
 
+/// Path -> bot. Replaced wholesale on every change, so a request only holds
+/// the lock long enough to clone the outer `Arc`.
+type EntryMap = Arc<HashMap<String, Arc<Entry>>>;
+
 /// Routes webhook updates to the correct bot by URL path segment.
-#[derive(Default)]
+///
+/// `BotMapping` is a cheap, cloneable handle: clones share the same bots, so
+/// bots can be added or removed through one clone while another serves
+/// requests.
+#[derive(Clone, Default)]
 pub struct BotMapping {
-    entries: HashMap<String, Entry>,
+    entries: Arc<RwLock<EntryMap>>,
+    /// Public URL the paths are served under, for `setWebhook`.
+    base_url: Option<String>,
 }
 
 impl BotMapping {
@@ -53,57 +83,211 @@ impl BotMapping {
         Self::default()
     }
 
-    /// Register a bot at the given URL path (e.g. `"/my_bot"`).
-    pub fn add_bot(&mut self, path: &str, bot: Bot, dispatcher: Dispatcher) {
-        let clean = path.trim_start_matches('/').to_string();
-        self.entries.insert(
-            clean,
-            Entry {
-                bot,
+    /// Public HTTPS URL the mapping is reachable at, e.g.
+    /// `"https://bots.example.com"`. When set, [`BotMapping::serve`] calls
+    /// `setWebhook` for every bot with `{base_url}/{path}` before listening.
+    pub fn webhook_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Register a bot at the given URL path (e.g. `"/my_bot"`), replacing
+    /// any bot already there.
+    pub fn add_bot(&self, path: &str, bot: Bot, dispatcher: Dispatcher) {
+        self.insert(path, bot, Arc::new(dispatcher), None);
+    }
+
+    /// Register a bot whose requests must carry `secret` in the
+    /// `X-Telegram-Bot-Api-Secret-Token` header. The secret is also passed to
+    /// `setWebhook` when webhooks are registered.
+    pub fn add_bot_with_secret(
+        &self,
+        path: &str,
+        bot: Bot,
+        dispatcher: Dispatcher,
+        secret: impl Into<String>,
+    ) {
+        self.insert(path, bot, Arc::new(dispatcher), Some(secret.into()));
+    }
+
+    /// Add a bot to a running mapping and call `setWebhook` for it (when a
+    /// [`BotMapping::webhook_base_url`] is set). The bot only starts
+    /// receiving requests once registration succeeded.
+    pub async fn register_bot(
+        &self,
+        path: &str,
+        bot: Bot,
+        dispatcher: Dispatcher,
+        secret: Option<String>,
+    ) -> Result<(), BotError> {
+        let path = clean(path);
+        self.set_webhook(&path, &bot, secret.as_deref()).await?;
+        self.insert(&path, bot, Arc::new(dispatcher), secret);
+        Ok(())
+    }
+
+    /// Stop routing requests for `path`. Updates already dispatched still
+    /// finish. Returns whether a bot was there.
+    pub fn remove_bot(&self, path: &str) -> bool {
+        self.take(path).is_some()
+    }
+
+    /// Remove the bot at `path` and call `deleteWebhook` for it (when a
+    /// [`BotMapping::webhook_base_url`] is set). Returns whether a bot was
+    /// there.
+    pub async fn unregister_bot(&self, path: &str) -> Result<bool, BotError> {
+        let Some(entry) = self.take(path) else {
+            return Ok(false);
+        };
+        if self.base_url.is_some() {
+            entry.bot.delete_webhook(None).await?;
+            info!(path = %redact_token(path), "webhook deleted");
+        }
+        Ok(true)
+    }
+
+    /// Swap the dispatcher of the bot at `path`. Updates already dispatched
+    /// finish on the old one. Returns whether a bot was there.
+    pub fn replace_dispatcher(&self, path: &str, dispatcher: Dispatcher) -> bool {
+        let path = clean(path);
+        self.update(|map| {
+            let Some(entry) = map.get(&path) else {
+                return false;
+            };
+            let entry = Arc::new(Entry {
+                bot: entry.bot.clone(),
                 dispatcher: Arc::new(dispatcher),
-            },
-        );
+                secret_token: entry.secret_token.clone(),
+            });
+            map.insert(path, entry);
+            true
+        })
+    }
+
+    /// Paths of all registered bots, without the leading `/`.
+    pub fn paths(&self) -> Vec<String> {
+        self.snapshot().keys().cloned().collect()
+    }
+
+    fn snapshot(&self) -> EntryMap {
+        Arc::clone(&self.entries.read().unwrap())
     }
 
-    /// Start an Axum HTTP server on `port` and block until the process exits.
+    fn get(&self, path: &str) -> Option<Arc<Entry>> {
+        self.snapshot().get(path.trim_start_matches('/')).cloned()
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut HashMap<String, Arc<Entry>>) -> R) -> R {
+        let mut entries = self.entries.write().unwrap();
+        let mut next = HashMap::clone(&entries);
+        let result = f(&mut next);
+        *entries = Arc::new(next);
+        result
+    }
+
+    fn insert(
+        &self,
+        path: &str,
+        bot: Bot,
+        dispatcher: Arc<Dispatcher>,
+        secret_token: Option<String>,
+    ) {
+        let entry = Arc::new(Entry {
+            bot,
+            dispatcher,
+            secret_token,
+        });
+        self.update(|map| map.insert(clean(path), entry));
+    }
+
+    fn take(&self, path: &str) -> Option<Arc<Entry>> {
+        self.update(|map| map.remove(&clean(path)))
+    }
+
+    async fn set_webhook(
+        &self,
+        path: &str,
+        bot: &Bot,
+        secret: Option<&str>,
+    ) -> Result<(), BotError> {
+        let Some(base) = &self.base_url else {
+            return Ok(());
+        };
+        let url = format!("{}/{}", base.trim_end_matches('/'), path);
+        let mut req = bot.set_webhook(url.clone());
+        if let Some(secret) = secret {
+            req = req.secret_token(secret.to_string());
+        }
+        req.await?;
+        info!(url = %redact_token(&url), "webhook registered");
+        Ok(())
+    }
+
+    /// Call `setWebhook` for every bot at `{base_url}/{path}`, with its
+    /// secret. Does nothing without [`BotMapping::webhook_base_url`].
+    ///
+    /// [`BotMapping::serve`] does this itself; call it when mounting
+    /// [`BotMapping::router`] into your own server.
+    pub async fn register_webhooks(&self) -> Result<(), BotError> {
+        for (path, entry) in self.snapshot().iter() {
+            self.set_webhook(path, &entry.bot, entry.secret_token.as_deref())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Register webhooks (see [`BotMapping::webhook_base_url`]), then start an
+    /// Axum HTTP server on `0.0.0.0:{port}` and block until the process exits.
     ///
     /// Each registered bot receives POST requests at `/{registered_path}`.
+    /// Keep a clone of the mapping to add or remove bots while it runs.
     pub async fn serve(self, port: u16) -> Result<(), BotError> {
-        let shared = Arc::new(self.entries);
-        let app = Router::new()
-            .route("/:path", post(handle_update))
-            .with_state(shared);
+        self.serve_at(SocketAddr::from(([0, 0, 0, 0], port))).await
+    }
 
-        let addr = format!("0.0.0.0:{port}");
-        let listener = tokio::net::TcpListener::bind(&addr)
+    /// Like [`BotMapping::serve`], but listen on `addr`.
+    pub async fn serve_at(self, addr: SocketAddr) -> Result<(), BotError> {
+        self.register_webhooks().await?;
+        let listener = tokio::net::TcpListener::bind(addr)
             .await
             .map_err(|e| BotError::Other(e.to_string()))?;
-        axum::serve(listener, app)
+        axum::serve(listener, self.router())
             .await
             .map_err(|e| BotError::Other(e.to_string()))
     }
+
+    /// An axum [`Router`] serving every registered bot at
+    /// `/{registered_path}`, for mounting into an existing application.
+    /// Bots added or removed later are picked up immediately.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/:path", post(handle_update))
+            .with_state(self.clone())
+    }
 }
 
-type EntryMap = Arc<HashMap<String, Entry>>;
+fn clean(path: &str) -> String {
+    path.trim_start_matches('/').to_string()
+}
 
 async fn handle_update(
     Path(path): Path<String>,
-    State(map): State<EntryMap>,
+    State(map): State<BotMapping>,
     headers: HeaderMap,
     body: Bytes,
 ) -> StatusCode {
-    let entry = match map.get(path.trim_start_matches('/')) {
+    let entry = match map.get(&path) {
         Some(e) => e,
         None => {
-            warn!(path = %path, "no bot registered for path");
+            warn!(path = %redact_token(&path), "no bot registered for path");
             return StatusCode::NOT_FOUND;
         }
     };
 
-    // Optional secret token check.
-    // Callers should set the Telegram secret via setWebhook; we expose the
-    // header here so integrators can validate it before forwarding.
-    let _ = headers.get("x-telegram-bot-api-secret-token");
+    if !secret_token_valid(&headers, entry.secret_token.as_deref()) {
+        warn!(path = %redact_token(&path), "invalid secret token - webhook request rejected");
+        return StatusCode::FORBIDDEN;
+    }
 
     let update: Update = match serde_json::from_slice(&body) {
         Ok(u) => u,
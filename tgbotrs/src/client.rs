@@ -33,11 +33,11 @@
 //! # }
 //! ```
 
-use std::fmt;
+use std::{fmt, path::PathBuf};
 
 use async_trait::async_trait;
 
-use crate::BotError;
+use crate::{input_file::BoxedReader, BotError};
 
 // Form abstraction
 /// A single field in a `multipart/form-data` request.
@@ -58,6 +58,36 @@ impl FormPart {
         }
     }
 
+    /// Copy this part for a repeated request. `None` for one-shot streams.
+    pub fn try_clone(&self) -> Option<Self> {
+        let body = match &self.body {
+            FormBody::Text(text) => FormBody::Text(text.clone()),
+            FormBody::Bytes {
+                filename,
+                mime,
+                data,
+            } => FormBody::Bytes {
+                filename: filename.clone(),
+                mime: mime.clone(),
+                data: data.clone(),
+            },
+            FormBody::Path {
+                filename,
+                mime,
+                path,
+            } => FormBody::Path {
+                filename: filename.clone(),
+                mime: mime.clone(),
+                path: path.clone(),
+            },
+            FormBody::Stream { .. } => return None,
+        };
+        Some(Self {
+            name: self.name.clone(),
+            body,
+        })
+    }
+
     /// Convenience constructor for a binary file part.
     pub fn bytes(
         name: impl Into<String>,
@@ -77,7 +107,6 @@ impl FormPart {
 }
 
 /// The body of a [`FormPart`].
-#[derive(Debug)]
 pub enum FormBody {
     /// A plain UTF-8 text value.
     Text(String),
@@ -90,6 +119,65 @@ pub enum FormBody {
         /// Raw file bytes.
         data: bytes::Bytes,
     },
+    /// A file on disk, streamed when the request is sent.
+    Path {
+        /// The file name sent to Telegram.
+        filename: String,
+        /// MIME type, e.g. `"video/mp4"`.
+        mime: String,
+        /// Local path to read from.
+        path: PathBuf,
+    },
+    /// An async reader, streamed when the request is sent.
+    Stream {
+        /// The file name sent to Telegram.
+        filename: String,
+        /// MIME type, e.g. `"video/mp4"`.
+        mime: String,
+        /// Source of the file contents.
+        reader: BoxedReader,
+        /// Content length, if known.
+        len: Option<u64>,
+    },
+}
+
+impl fmt::Debug for FormBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormBody::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            FormBody::Bytes {
+                filename,
+                mime,
+                data,
+            } => f
+                .debug_struct("Bytes")
+                .field("filename", filename)
+                .field("mime", mime)
+                .field("data", data)
+                .finish(),
+            FormBody::Path {
+                filename,
+                mime,
+                path,
+            } => f
+                .debug_struct("Path")
+                .field("filename", filename)
+                .field("mime", mime)
+                .field("path", path)
+                .finish(),
+            FormBody::Stream {
+                filename,
+                mime,
+                len,
+                ..
+            } => f
+                .debug_struct("Stream")
+                .field("filename", filename)
+                .field("mime", mime)
+                .field("len", len)
+                .finish_non_exhaustive(),
+        }
+    }
 }
 
 // BotClient trait
@@ -108,6 +196,74 @@ pub trait BotClient: Send + Sync + fmt::Debug {
     /// POST a `multipart/form-data` request to `url` and return the raw
     /// response bytes.
     async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError>;
+
+    /// GET `url` and return the raw response bytes. Used for file downloads.
+    ///
+    /// The default implementation returns an error; implement it to support
+    /// [`Bot::download_file`](crate::Bot::download_file) and friends.
+    async fn get(&self, _url: &str) -> Result<bytes::Bytes, BotError> {
+        // The URL holds the bot token, so it stays out of the message.
+        Err(BotError::Other(
+            "this client does not support GET requests".into(),
+        ))
+    }
+
+    /// GET `url` and return the response body as a reader.
+    ///
+    /// The default implementation buffers [`BotClient::get`]. Override it to
+    /// stream large downloads.
+    async fn get_stream(&self, url: &str) -> Result<ResponseBody, BotError> {
+        let data = self.get(url).await?;
+        Ok(ResponseBody {
+            len: Some(data.len() as u64),
+            reader: Box::pin(std::io::Cursor::new(data)),
+        })
+    }
+}
+
+/// Shared clients are clients too, so wrappers can hold `Arc<dyn BotClient>`.
+#[async_trait]
+impl<C: BotClient + ?Sized> BotClient for std::sync::Arc<C> {
+    async fn post_json(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<bytes::Bytes, BotError> {
+        (**self).post_json(url, body).await
+    }
+
+    async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+        (**self).post_form(url, parts).await
+    }
+
+    async fn get(&self, url: &str) -> Result<bytes::Bytes, BotError> {
+        (**self).get(url).await
+    }
+
+    async fn get_stream(&self, url: &str) -> Result<ResponseBody, BotError> {
+        (**self).get_stream(url).await
+    }
+}
+
+/// The Bot API method an endpoint URL points at (its last path segment).
+pub(crate) fn method_name(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or(url)
+}
+
+/// A streamed response body returned by [`BotClient::get_stream`].
+pub struct ResponseBody {
+    /// Source of the body contents.
+    pub reader: BoxedReader,
+    /// Content length, if known.
+    pub len: Option<u64>,
+}
+
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBody")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
 }
 
 // Default reqwest implementation
@@ -126,7 +282,7 @@ impl ReqwestClient {
         let inner = reqwest::Client::builder()
             .timeout(timeout)
             .build()
-            .map_err(BotError::Http)?;
+            .map_err(BotError::from)?;
         Ok(Self { inner })
     }
 }
@@ -138,62 +294,132 @@ impl BotClient for ReqwestClient {
         url: &str,
         body: serde_json::Value,
     ) -> Result<bytes::Bytes, BotError> {
-        self.inner
-            .post(url)
+        with_headers(self.inner.post(url))
             .json(&body)
             .send()
             .await
-            .map_err(BotError::Http)?
+            .map_err(BotError::from)?
+            .bytes()
+            .await
+            .map_err(BotError::from)
+    }
+
+    async fn get(&self, url: &str) -> Result<bytes::Bytes, BotError> {
+        with_headers(self.inner.get(url))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(BotError::from)?
             .bytes()
             .await
-            .map_err(BotError::Http)
+            .map_err(BotError::from)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn get_stream(&self, url: &str) -> Result<ResponseBody, BotError> {
+        use futures_util::TryStreamExt;
+
+        let resp = with_headers(self.inner.get(url))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(BotError::from)?;
+        let len = resp.content_length();
+        let stream = resp.bytes_stream().map_err(std::io::Error::other);
+        Ok(ResponseBody {
+            reader: Box::pin(tokio_util::io::StreamReader::new(stream)),
+            len,
+        })
     }
 
     async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
         let mut form = reqwest::multipart::Form::new();
 
         for part in parts {
-            match part.body {
-                FormBody::Text(text) => {
-                    form = form.text(part.name, text);
-                }
-                FormBody::Bytes { .. } => {
-                    // Binary file uploads are not supported on WASM targets because
-                    // the multipart/form-data attachment API is unavailable in that
-                    // environment.  Use InputFile::FileId or a URL string instead.
-                    #[cfg(target_arch = "wasm32")]
-                    return Err(BotError::Other(
-                        "file uploads are not supported on WASM; use file_id or a URL".into(),
-                    ));
-
-                    #[cfg(not(target_arch = "wasm32"))]
-                    {
-                        let FormBody::Bytes {
-                            filename,
-                            mime,
-                            data,
-                        } = part.body
-                        else {
-                            unreachable!()
-                        };
-                        let rpart = reqwest::multipart::Part::bytes(data.to_vec())
-                            .file_name(filename)
-                            .mime_str(&mime)
-                            .map_err(|e| BotError::Other(e.to_string()))?;
-                        form = form.part(part.name, rpart);
-                    }
-                }
+            if let FormBody::Text(text) = part.body {
+                form = form.text(part.name, text);
+                continue;
+            }
+
+            // Binary file uploads are not supported on WASM targets because
+            // the multipart/form-data attachment API is unavailable in that
+            // environment.  Use InputFile::FileId or a URL string instead.
+            #[cfg(target_arch = "wasm32")]
+            return Err(BotError::Other(
+                "file uploads are not supported on WASM; use file_id or a URL".into(),
+            ));
+
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let rpart = file_part(part.body).await?;
+                form = form.part(part.name, rpart);
             }
         }
 
-        self.inner
-            .post(url)
+        with_headers(self.inner.post(url))
             .multipart(form)
             .send()
             .await
-            .map_err(BotError::Http)?
+            .map_err(BotError::from)?
             .bytes()
             .await
-            .map_err(BotError::Http)
+            .map_err(BotError::from)
     }
 }
+
+/// Apply headers requested by [`HeaderLayer`](crate::layer::HeaderLayer)s.
+fn with_headers(mut req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    for (name, value) in crate::layer::request_headers() {
+        req = req.header(name, value);
+    }
+    req
+}
+
+/// Build a reqwest file part. Disk files and readers are streamed, never
+/// buffered in full.
+#[cfg(not(target_arch = "wasm32"))]
+async fn file_part(body: FormBody) -> Result<reqwest::multipart::Part, BotError> {
+    use reqwest::{multipart::Part, Body};
+    use tokio_util::io::ReaderStream;
+
+    let (part, filename, mime) = match body {
+        FormBody::Text(text) => return Ok(Part::text(text)),
+        FormBody::Bytes {
+            filename,
+            mime,
+            data,
+        } => (Part::bytes(data.to_vec()), filename, mime),
+        FormBody::Path {
+            filename,
+            mime,
+            path,
+        } => {
+            let file = tokio::fs::File::open(&path).await.map_err(|e| {
+                BotError::Other(format!("failed to open {}: {}", path.display(), e))
+            })?;
+            let len = file
+                .metadata()
+                .await
+                .map_err(|e| BotError::Other(format!("failed to stat {}: {}", path.display(), e)))?
+                .len();
+            let body = Body::wrap_stream(ReaderStream::new(file));
+            (Part::stream_with_length(body, len), filename, mime)
+        }
+        FormBody::Stream {
+            filename,
+            mime,
+            reader,
+            len,
+        } => {
+            let body = Body::wrap_stream(ReaderStream::new(reader));
+            let part = match len {
+                Some(len) => Part::stream_with_length(body, len),
+                None => Part::stream(body),
+            };
+            (part, filename, mime)
+        }
+    };
+    part.file_name(filename)
+        .mime_str(&mime)
+        .map_err(|e| BotError::Other(e.to_string()))
+}
@@ -21,19 +21,27 @@
 use serde_json::Value;
 use ureq::Agent;
 
-use crate::BotError;
+use crate::{error::redact_token, BotError};
 
 /// A synchronous, blocking Telegram bot client backed by `ureq`.
 ///
 /// This wraps the raw JSON API; all methods are blocking and return
 /// `Result<serde_json::Value, BotError>`. Use the async [`Bot`](crate::Bot)
 /// for production bots; `SyncBot` is best for scripts and CLI tools.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct SyncBot {
     api_url: String,
     agent: Agent,
 }
 
+impl std::fmt::Debug for SyncBot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncBot")
+            .field("api_url", &redact_token(&self.api_url))
+            .finish_non_exhaustive()
+    }
+}
+
 impl SyncBot {
     /// Create a `SyncBot` using the default Telegram API URL.
     pub fn new(token: &str) -> Self {
@@ -62,16 +70,16 @@ impl SyncBot {
             self.agent
                 .post(&url)
                 .call()
-                .map_err(|e| BotError::Other(e.to_string()))?
+                .map_err(|e| BotError::Other(redact_token(&e.to_string()).into_owned()))?
                 .into_json()
-                .map_err(|e| BotError::Other(e.to_string()))?
+                .map_err(|e| BotError::Other(redact_token(&e.to_string()).into_owned()))?
         } else {
             self.agent
                 .post(&url)
                 .send_json(params)
-                .map_err(|e| BotError::Other(e.to_string()))?
+                .map_err(|e| BotError::Other(redact_token(&e.to_string()).into_owned()))?
                 .into_json()
-                .map_err(|e| BotError::Other(e.to_string()))?
+                .map_err(|e| BotError::Other(redact_token(&e.to_string()).into_owned()))?
         };
 
         if response["ok"].as_bool().unwrap_or(false) {
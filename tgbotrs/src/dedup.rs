@@ -0,0 +1,177 @@
+//! Dropping duplicate deliveries of the same update.
+//!
+//! Telegram redelivers a webhook update when the endpoint is slow or answers
+//! with an error, and a restarted [`Poller`](crate::Poller) may fetch updates
+//! it already handed out. A [`DedupStore`] remembers recently seen
+//! `update_id`s so handlers run once per update.
+//!
+//! Enable it with [`DispatcherOpts::dedup`](crate::DispatcherOpts::dedup) for
+//! anything built on a [`Dispatcher`](crate::Dispatcher), such as
+//! [`Updater`](crate::Updater) and `BotMapping`, or with
+//! `WebhookServer::dedup` for a plain webhook handler or stream.
+//!
+//! An update counts as seen once it has been accepted, not once it has been
+//! handled: if the process dies mid-handler, a redelivery is still dropped.
+//! Updates that could not be accepted, e.g. because a webhook stream was
+//! dropped, are forgotten again so Telegram's redelivery gets through.
+//! Deployments with several replicas can share one store by implementing
+//! [`DedupStore`] on top of e.g. Redis `SET NX EX`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tgbotrs::{Dispatcher, DispatcherOpts, InMemoryDedupStore};
+//!
+//! let dp = Dispatcher::new(
+//!     DispatcherOpts::default().dedup(InMemoryDedupStore::new(10_000, Duration::from_secs(600))),
+//! );
+//! ```
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::BotError;
+
+/// Remembers which updates were already seen.
+///
+/// `update_id`s are only unique per bot, so both ids form the key.
+#[async_trait]
+pub trait DedupStore: Send + Sync {
+    /// Record the update and return whether this is the first time it was
+    /// seen. Must be atomic when the store is shared between processes.
+    async fn first_seen(&self, bot_id: i64, update_id: i64) -> Result<bool, BotError>;
+
+    /// Undo [`DedupStore::first_seen`] for an update that was not accepted,
+    /// so its redelivery is handled.
+    async fn forget(&self, bot_id: i64, update_id: i64) -> Result<(), BotError>;
+}
+
+#[async_trait]
+impl<S: DedupStore + ?Sized> DedupStore for Box<S> {
+    async fn first_seen(&self, bot_id: i64, update_id: i64) -> Result<bool, BotError> {
+        (**self).first_seen(bot_id, update_id).await
+    }
+
+    async fn forget(&self, bot_id: i64, update_id: i64) -> Result<(), BotError> {
+        (**self).forget(bot_id, update_id).await
+    }
+}
+
+#[async_trait]
+impl<S: DedupStore + ?Sized> DedupStore for Arc<S> {
+    async fn first_seen(&self, bot_id: i64, update_id: i64) -> Result<bool, BotError> {
+        (**self).first_seen(bot_id, update_id).await
+    }
+
+    async fn forget(&self, bot_id: i64, update_id: i64) -> Result<(), BotError> {
+        (**self).forget(bot_id, update_id).await
+    }
+}
+
+/// Whether to handle an update, failing open if the store is unavailable.
+pub(crate) async fn is_new(store: &dyn DedupStore, bot_id: i64, update_id: i64) -> bool {
+    match store.first_seen(bot_id, update_id).await {
+        Ok(true) => true,
+        Ok(false) => {
+            tracing::debug!(update_id, "duplicate update dropped");
+            false
+        }
+        Err(e) => {
+            tracing::warn!(update_id, error = %e, "dedup store failed - handling update");
+            true
+        }
+    }
+}
+
+/// Forget an update that was not accepted after all.
+#[cfg(feature = "webhook")]
+pub(crate) async fn forget(store: &dyn DedupStore, bot_id: i64, update_id: i64) {
+    if let Err(e) = store.forget(bot_id, update_id).await {
+        tracing::warn!(update_id, error = %e, "dedup store failed to forget update");
+    }
+}
+
+/// Remembers up to `capacity` updates for at most `ttl` each, in memory.
+///
+/// Cloning shares the window, e.g. between several dispatchers.
+#[derive(Debug, Clone)]
+pub struct InMemoryDedupStore {
+    capacity: usize,
+    ttl: Duration,
+    window: Arc<Mutex<Window>>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    seen: HashSet<(i64, i64)>,
+    /// Insertion order, oldest first.
+    order: VecDeque<((i64, i64), Instant)>,
+}
+
+impl InMemoryDedupStore {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            window: Arc::default(),
+        }
+    }
+
+    /// Number of updates currently remembered.
+    pub fn len(&self) -> usize {
+        self.window.lock().unwrap().seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for InMemoryDedupStore {
+    /// 10 000 updates for up to 10 minutes.
+    fn default() -> Self {
+        Self::new(10_000, Duration::from_secs(600))
+    }
+}
+
+#[async_trait]
+impl DedupStore for InMemoryDedupStore {
+    async fn first_seen(&self, bot_id: i64, update_id: i64) -> Result<bool, BotError> {
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        while let Some(&(key, at)) = window.order.front() {
+            if now.duration_since(at) < self.ttl {
+                break;
+            }
+            window.order.pop_front();
+            window.seen.remove(&key);
+        }
+        let key = (bot_id, update_id);
+        if !window.seen.insert(key) {
+            return Ok(false);
+        }
+        window.order.push_back((key, now));
+        if window.order.len() > self.capacity {
+            if let Some((oldest, _)) = window.order.pop_front() {
+                window.seen.remove(&oldest);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn forget(&self, bot_id: i64, update_id: i64) -> Result<(), BotError> {
+        let key = (bot_id, update_id);
+        let mut window = self.window.lock().unwrap();
+        if window.seen.remove(&key) {
+            window.order.retain(|&(k, _)| k != key);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! File downloads through the bot's [`BotClient`](crate::client::BotClient).
+//!
+//! Every download goes through the pluggable transport, so a mock client that
+//! implements [`BotClient::get`](crate::client::BotClient::get) can serve
+//! fixture files in tests. When the bot talks to a local Bot API server
+//! (`--local` mode), `getFile` returns an absolute path on the server's disk;
+//! such files are read straight from the filesystem instead.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use tgbotrs::Bot;
+//!
+//! # async fn example(bot: Bot, file_id: &str) -> Result<(), tgbotrs::BotError> {
+//! // Small files: load into memory.
+//! let bytes = bot.download_file_by_id(file_id).await?;
+//! println!("{} bytes", bytes.len());
+//!
+//! // Large files: stream to disk with progress.
+//! let file = bot.get_file(file_id).await?;
+//! let mut out = tokio::fs::File::create("video.mp4").await?;
+//! bot.download_file_with_progress(&file, &mut out, |done, total| {
+//!     println!("{done}/{}", total.map_or("?".into(), |t| t.to_string()));
+//! })
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{client::ResponseBody, types::File, Bot, BotError};
+
+impl Bot {
+    /// Download a file into memory.
+    ///
+    /// `file` must have `file_path` set, as returned by `getFile`.
+    pub async fn download_file(&self, file: &File) -> Result<Bytes, BotError> {
+        let path = file_path(file)?;
+        if Path::new(path).is_absolute() {
+            return Ok(tokio::fs::read(path).await?.into());
+        }
+        self.client.get(&self.file_url(path)).await
+    }
+
+    /// Call `getFile` for `file_id`, then download it into memory.
+    pub async fn download_file_by_id(&self, file_id: impl Into<String>) -> Result<Bytes, BotError> {
+        let file = self.get_file(file_id).await?;
+        self.download_file(&file).await
+    }
+
+    /// Stream a file into `writer` without buffering it in memory.
+    ///
+    /// Returns the number of bytes written.
+    pub async fn download_file_to<W>(&self, file: &File, writer: &mut W) -> Result<u64, BotError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        self.download_file_with_progress(file, writer, |_, _| {})
+            .await
+    }
+
+    /// Like [`Bot::download_file_to`], calling `progress(downloaded, total)`
+    /// after every chunk. `total` comes from the response length or
+    /// `file.file_size`, and is `None` when neither is known.
+    pub async fn download_file_with_progress<W, F>(
+        &self,
+        file: &File,
+        writer: &mut W,
+        mut progress: F,
+    ) -> Result<u64, BotError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+        F: FnMut(u64, Option<u64>),
+    {
+        let path = file_path(file)?;
+        let ResponseBody { mut reader, len } = if Path::new(path).is_absolute() {
+            let f = tokio::fs::File::open(path).await?;
+            let len = f.metadata().await?.len();
+            ResponseBody {
+                reader: Box::pin(f),
+                len: Some(len),
+            }
+        } else {
+            self.client.get_stream(&self.file_url(path)).await?
+        };
+        let total = len.or_else(|| file.file_size.map(|s| s as u64));
+
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut done = 0u64;
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n]).await?;
+            done += n as u64;
+            progress(done, total);
+        }
+        writer.flush().await?;
+        Ok(done)
+    }
+
+    pub(crate) fn file_url(&self, path: &str) -> String {
+        format!("{}/file/bot{}/{}", self.api_url, self.token, path)
+    }
+}
+
+fn file_path(file: &File) -> Result<&str, BotError> {
+    file.file_path.as_deref().ok_or_else(|| {
+        BotError::Other(format!(
+            "file {} has no file_path; call get_file first",
+            file.file_id
+        ))
+    })
+}
@@ -1,15 +1,22 @@
+use std::{borrow::Cow, sync::OnceLock};
+
+use regex::{Captures, Regex};
 use thiserror::Error;
 
 /// The main error type for tgbotrs.
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum BotError {
-    #[error("HTTP error: {0}")]
-    Http(#[from] reqwest::Error),
+    /// Transport failure. URLs in the message never contain the bot token.
+    #[error("HTTP error: {}", redact_token(&.0.to_string()))]
+    Http(#[source] reqwest::Error),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
     /// Telegram returned `ok: false`.
     #[error("Telegram API error [{code}]: {description}")]
     Api {
@@ -41,4 +48,187 @@ impl BotError {
         }
         None
     }
+
+    /// Classify a Telegram API error. `None` for non-API errors.
+    pub fn kind(&self) -> Option<ApiErrorKind> {
+        match self {
+            BotError::Api {
+                code, description, ..
+            } => Some(ApiErrorKind::parse(*code, description)),
+            _ => None,
+        }
+    }
+
+    /// The user blocked the bot.
+    pub fn is_blocked_by_user(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::BotBlocked)
+    }
+
+    /// The user deleted their account.
+    pub fn is_user_deactivated(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::UserDeactivated)
+    }
+
+    /// The chat doesn't exist or the bot can't see it.
+    pub fn is_chat_not_found(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::ChatNotFound)
+    }
+
+    /// An edit left the message exactly as it was.
+    pub fn is_message_not_modified(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::MessageNotModified)
+    }
+
+    /// The message to edit was deleted or never existed.
+    pub fn is_message_to_edit_not_found(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::MessageToEditNotFound)
+    }
+
+    /// The message to delete was already deleted or never existed.
+    pub fn is_message_to_delete_not_found(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::MessageToDeleteNotFound)
+    }
+
+    /// `getUpdates` clashed with an active webhook or another poller.
+    pub fn is_conflict(&self) -> bool {
+        self.kind() == Some(ApiErrorKind::Conflict)
+    }
+}
+
+/// Well-known Telegram API failures, recognised from the error description.
+///
+/// Telegram documents error codes but not descriptions, so matching is
+/// best-effort; anything unrecognised is [`ApiErrorKind::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ApiErrorKind {
+    /// "Forbidden: bot was blocked by the user"
+    BotBlocked,
+    /// "Forbidden: user is deactivated"
+    UserDeactivated,
+    /// "Forbidden: bot was kicked from the group chat"
+    BotKicked,
+    /// "Forbidden: bot is not a member of the channel chat"
+    BotNotMember,
+    /// "Forbidden: bot can't initiate conversation with a user"
+    CantInitiateConversation,
+    /// "Forbidden: bot can't send messages to bots"
+    CantSendToBots,
+    /// "Bad Request: chat not found"
+    ChatNotFound,
+    /// "Bad Request: user not found"
+    UserNotFound,
+    /// "Bad Request: message is not modified"
+    MessageNotModified,
+    /// "Bad Request: message to edit not found"
+    MessageToEditNotFound,
+    /// "Bad Request: message to delete not found"
+    MessageToDeleteNotFound,
+    /// "Bad Request: message to reply not found"
+    MessageToReplyNotFound,
+    /// "Bad Request: message can't be edited"
+    MessageCantBeEdited,
+    /// "Bad Request: message can't be deleted"
+    MessageCantBeDeleted,
+    /// "Bad Request: message text is empty"
+    MessageTextEmpty,
+    /// "Bad Request: message is too long"
+    MessageTooLong,
+    /// "Bad Request: can't parse entities"
+    CantParseEntities,
+    /// "Bad Request: not enough rights ..." or "have no rights ..."
+    NotEnoughRights,
+    /// "Bad Request: group chat was upgraded to a supergroup chat"
+    GroupMigrated,
+    /// "Bad Request: query is too old and response timeout expired"
+    QueryTooOld,
+    /// "Bad Request: wrong file identifier/HTTP URL specified"
+    InvalidFileId,
+    /// 401 Unauthorized: the token was revoked.
+    Unauthorized,
+    /// 409 Conflict: another `getUpdates` call or an active webhook.
+    Conflict,
+    /// 429 Too Many Requests; see [`BotError::flood_wait_seconds`].
+    TooManyRequests,
+    /// Anything else.
+    Unknown,
+}
+
+impl ApiErrorKind {
+    /// Classify an error from its `error_code` and `description`.
+    pub fn parse(code: i64, description: &str) -> Self {
+        use ApiErrorKind::*;
+        match code {
+            401 => return Unauthorized,
+            409 => return Conflict,
+            429 => return TooManyRequests,
+            _ => {}
+        }
+        let d = description.to_ascii_lowercase();
+        const PATTERNS: &[(&str, ApiErrorKind)] = &[
+            ("bot was blocked by the user", BotBlocked),
+            ("user is deactivated", UserDeactivated),
+            ("bot was kicked", BotKicked),
+            ("bot is not a member", BotNotMember),
+            ("can't initiate conversation", CantInitiateConversation),
+            ("can't send messages to bots", CantSendToBots),
+            ("chat not found", ChatNotFound),
+            ("user not found", UserNotFound),
+            ("message is not modified", MessageNotModified),
+            ("message to edit not found", MessageToEditNotFound),
+            ("message to delete not found", MessageToDeleteNotFound),
+            ("message to reply not found", MessageToReplyNotFound),
+            ("message can't be edited", MessageCantBeEdited),
+            ("message can't be deleted", MessageCantBeDeleted),
+            ("message text is empty", MessageTextEmpty),
+            ("message is too long", MessageTooLong),
+            ("can't parse entities", CantParseEntities),
+            ("not enough rights", NotEnoughRights),
+            ("have no rights", NotEnoughRights),
+            ("upgraded to a supergroup", GroupMigrated),
+            ("query is too old", QueryTooOld),
+            ("wrong file identifier", InvalidFileId),
+            ("invalid file_id", InvalidFileId),
+            ("wrong remote file identifier", InvalidFileId),
+        ];
+        PATTERNS
+            .iter()
+            .find(|(needle, _)| d.contains(needle))
+            .map_or(Unknown, |(_, kind)| *kind)
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    /// Strips the bot token from the request URL carried by the error, so
+    /// neither `Display` nor `Debug` can leak it.
+    fn from(mut e: reqwest::Error) -> Self {
+        if let Some(url) = e.url_mut() {
+            let path = redact_token(url.path()).into_owned();
+            url.set_path(&path);
+        }
+        BotError::Http(e)
+    }
+}
+
+/// Show only the bot ID part of a token: `123456:***`.
+pub(crate) fn mask_token(token: &str) -> String {
+    match token.split_once(':') {
+        Some((id, _)) => format!("{id}:***"),
+        None => "***".into(),
+    }
+}
+
+/// Replace bot tokens in `text` (as in `/bot<token>/` URL segments, or bare
+/// tokens) with their masked form.
+pub(crate) fn redact_token(text: &str) -> Cow<'_, str> {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let re = TOKEN.get_or_init(|| Regex::new(r"(bot)?(\d+):([A-Za-z0-9_-]+)").unwrap());
+    re.replace_all(text, |c: &Captures<'_>| {
+        // Without the `bot` prefix only mask things that look like real tokens.
+        if c.get(1).is_some() || c[3].len() >= 30 {
+            format!("{}{}:***", c.get(1).map_or("", |m| m.as_str()), &c[2])
+        } else {
+            c[0].to_string()
+        }
+    })
 }
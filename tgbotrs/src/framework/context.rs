@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
-use crate::types::{Chat, MaybeInaccessibleMessage, Message, Update, User};
+use crate::{
+    framework::extensions::Extensions,
+    types::{Chat, MaybeInaccessibleMessage, Message, Update, User},
+};
 
 /// Per-update context passed to every handler.
 #[derive(Debug, Clone)]
@@ -8,18 +11,38 @@ pub struct Context {
     pub update: Update,
     /// Shared data bag for passing values between handlers.
     pub data: HashMap<String, String>,
+    /// Typed values for this update only, e.g. set by middleware.
+    pub extensions: Extensions,
+    /// Application state from [`DispatcherOpts::state`](crate::DispatcherOpts::state).
+    pub(crate) state: Arc<Extensions>,
     pub(crate) args: Vec<String>,
 }
 
 impl Context {
     pub fn new(update: Update) -> Self {
+        Self::with_state(update, Arc::default())
+    }
+
+    pub(crate) fn with_state(update: Update, state: Arc<Extensions>) -> Self {
         Self {
             update,
             data: HashMap::new(),
+            extensions: Extensions::new(),
+            state,
             args: Vec::new(),
         }
     }
 
+    /// Application state of type `T` registered on the dispatcher.
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get()
+    }
+
+    /// The value of type `T` attached to this update.
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
     /// Chat this update belongs to.
     pub fn effective_chat(&self) -> Option<&Chat> {
         let u = &self.update;
@@ -2,21 +2,43 @@
 //!
 //! Within a group the first matching handler runs; dispatcher then moves to the next group.
 //! Handlers can alter flow by returning `Err(ContinueGroups)` or `Err(EndGroups)`.
+//!
+//! By default every update runs in its own task, so two updates from the same
+//! chat may be handled concurrently. With [`DispatcherOpts::ordered_by`],
+//! updates that share an [`UpdateKey`] are handled one at a time, in arrival
+//! order, while different keys still run in parallel. Nothing is dropped
+//! unless [`DispatcherOpts::ordered_queue_size`] caps the queue per key.
+//!
+//! [`Middleware`]s added with [`DispatcherOpts::middleware`] wrap the group
+//! iteration of every dispatched update.
+//!
+//! With [`DispatcherOpts::dedup`], updates whose `update_id` was seen
+//! recently are dropped before any handler runs.
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 
-use tokio::sync::Semaphore;
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot, Semaphore,
+    },
+    task::{AbortHandle, JoinSet},
+};
 use tracing::{debug, error, warn};
 
 use crate::{
+    dedup::{self, DedupStore},
     framework::{
         context::Context,
+        extensions::Extensions,
         handler::{ContinueGroups, EndGroups, Handler},
+        middleware::{DispatchOutcome, Middleware, Next},
     },
+    shutdown::{InFlight, InFlightGuard},
     types::Update,
     Bot,
 };
@@ -36,12 +58,54 @@ pub type ErrorHook = Arc<
 
 pub type PanicHook = Arc<dyn std::ops::Fn(&Bot, &Context, String) + Send + Sync>;
 
+pub type UpdateKeyFn = Arc<dyn std::ops::Fn(&Context) -> Option<String> + Send + Sync>;
+
+/// Which updates [`DispatcherOpts::ordered_by`] handles one after another.
+///
+/// Updates without a key (e.g. an inline query has no chat) are not ordered.
+#[derive(Clone)]
+pub enum UpdateKey {
+    /// Updates from the same chat.
+    Chat,
+    /// Updates from the same user.
+    User,
+    /// Updates for which the function returns the same key.
+    Custom(UpdateKeyFn),
+}
+
+impl UpdateKey {
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: std::ops::Fn(&Context) -> Option<String> + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(f))
+    }
+
+    fn key(&self, ctx: &Context) -> Option<String> {
+        match self {
+            Self::Chat => ctx.effective_chat().map(|c| c.id.to_string()),
+            Self::User => ctx.effective_user().map(|u| u.id.to_string()),
+            Self::Custom(f) => f(ctx),
+        }
+    }
+}
+
 /// Options for `Dispatcher::new`.
 #[derive(Clone, Default)]
 pub struct DispatcherOpts {
     pub max_routines: Option<usize>,
     pub error_handler: Option<ErrorHook>,
     pub panic_handler: Option<PanicHook>,
+    /// Handle updates sharing this key sequentially.
+    pub ordered_by: Option<UpdateKey>,
+    /// Updates that may wait per key when ordered (default: unlimited).
+    pub ordered_queue_size: Option<usize>,
+    /// Drop updates this store has already seen.
+    pub dedup: Option<Arc<dyn DedupStore>>,
+    /// Wrap every update, outermost first.
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    /// Application state handlers read with [`Context::state`].
+    pub state: Extensions,
 }
 
 impl DispatcherOpts {
@@ -50,6 +114,45 @@ impl DispatcherOpts {
         self
     }
 
+    /// Handle updates that share `key` one at a time, in arrival order.
+    /// Updates with different keys still run in parallel, within
+    /// `max_routines`.
+    pub fn ordered_by(mut self, key: UpdateKey) -> Self {
+        self.ordered_by = Some(key);
+        self
+    }
+
+    /// Let at most `n` updates wait behind the one being handled for the
+    /// same key, and drop the rest with a warning. Off by default: queues
+    /// grow as needed. Dropped updates are lost for good, as polling has
+    /// usually confirmed them to Telegram already.
+    pub fn ordered_queue_size(mut self, n: usize) -> Self {
+        self.ordered_queue_size = Some(n);
+        self
+    }
+
+    /// Drop updates whose `update_id` `store` has already seen, e.g. webhook
+    /// redeliveries. See [`crate::dedup`].
+    pub fn dedup(mut self, store: impl DedupStore + 'static) -> Self {
+        self.dedup = Some(Arc::new(store));
+        self
+    }
+
+    /// Append `middleware` to the chain around the handler groups. Added
+    /// middlewares run inside the ones added before them.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Make `value` available to every handler and middleware through
+    /// [`Context::state`]. One value per type; wrap shared resources such as
+    /// a database pool in your own type if needed.
+    pub fn state<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+
     pub fn on_error<F>(mut self, f: F) -> Self
     where
         F: std::ops::Fn(&Bot, &Context, &(dyn Error + Send + Sync)) -> DispatcherAction
@@ -72,11 +175,31 @@ impl DispatcherOpts {
 
 type HandlerMap = BTreeMap<i32, Vec<Arc<dyn Handler>>>;
 
+/// An update waiting in a per-key queue.
+struct Job {
+    bot: Bot,
+    ctx: Context,
+    done: oneshot::Sender<()>,
+    _guard: InFlightGuard,
+}
+
+/// Per-key queues of ordered updates; each has one worker task draining it.
+type Queues = Arc<Mutex<HashMap<String, mpsc::Sender<Job>>>>;
+
 pub struct Dispatcher {
     handlers: Arc<RwLock<HandlerMap>>,
     error_handler: Option<ErrorHook>,
     panic_handler: Option<PanicHook>,
     semaphore: Option<Arc<Semaphore>>,
+    in_flight: Arc<InFlight>,
+    /// Spawned tasks stay detached; these only let shutdown abort them.
+    tasks: Mutex<Vec<AbortHandle>>,
+    ordered_by: Option<UpdateKey>,
+    queue_capacity: usize,
+    queues: Queues,
+    dedup: Option<Arc<dyn DedupStore>>,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+    state: Arc<Extensions>,
 }
 
 impl Dispatcher {
@@ -86,6 +209,18 @@ impl Dispatcher {
             error_handler: opts.error_handler,
             panic_handler: opts.panic_handler,
             semaphore: opts.max_routines.map(|n| Arc::new(Semaphore::new(n))),
+            in_flight: Arc::default(),
+            tasks: Mutex::default(),
+            ordered_by: opts.ordered_by,
+            // One extra slot for the update the worker is about to take. The
+            // channel only allocates as it fills, so "unlimited" is cheap.
+            queue_capacity: opts
+                .ordered_queue_size
+                .map_or(Semaphore::MAX_PERMITS, |n| n.max(1) + 1),
+            queues: Queues::default(),
+            dedup: opts.dedup,
+            middlewares: opts.middlewares.into(),
+            state: Arc::new(opts.state),
         }
     }
 
@@ -129,88 +264,104 @@ impl Dispatcher {
     }
 
     /// Dispatch an update, spawning a Tokio task. Returns immediately.
+    ///
+    /// With [`DispatcherOpts::ordered_by`], the update is queued behind
+    /// earlier updates with the same key instead.
     pub fn dispatch(&self, bot: Bot, update: Update) {
-        let handlers_arc = Arc::clone(&self.handlers);
-        let error_hook = self.error_handler.clone();
-        let panic_hook = self.panic_handler.clone();
-        let semaphore = self.semaphore.clone();
-
-        tokio::spawn(async move {
-            let _permit = if let Some(sem) = &semaphore {
-                Some(sem.clone().acquire_owned().await.ok())
-            } else {
-                None
-            };
-
-            let ctx = Context::new(update);
-            // Snapshot the entire handler map once — single read lock, no per-group re-lock.
-            let snapshot: HandlerMap = handlers_arc.read().unwrap().clone();
-
-            'groups: for (group, handlers) in snapshot {
-                for handler in handlers {
-                    if !handler.check_update(&ctx) {
-                        continue;
-                    }
+        self.submit(bot, update);
+    }
 
-                    debug!(handler = handler.name(), group, "matched");
-
-                    let h = Arc::clone(&handler);
-                    let bot2 = bot.clone();
-                    let ctx2 = ctx.clone();
-                    let join = tokio::spawn(async move { h.handle_update(bot2, ctx2).await });
-
-                    match join.await {
-                        Err(e) if e.is_panic() => {
-                            let msg = e
-                                .into_panic()
-                                .downcast::<String>()
-                                .map(|s| *s)
-                                .or_else(|p| p.downcast::<&str>().map(|s| s.to_string()))
-                                .unwrap_or_else(|_| "<non-string panic>".into());
-                            if let Some(hook) = &panic_hook {
-                                hook(&bot, &ctx, msg);
-                            } else {
-                                error!(handler = handler.name(), group, panic = %msg, "panicked");
-                            }
-                            break;
-                        }
-                        Err(e) => {
-                            error!(handler = handler.name(), group, error = %e, "task aborted");
-                            break;
-                        }
-                        Ok(Err(e)) => {
-                            if e.is::<ContinueGroups>() {
-                                debug!(handler = handler.name(), "ContinueGroups");
-                                continue;
-                            }
-                            if e.is::<EndGroups>() {
-                                debug!(handler = handler.name(), "EndGroups");
-                                break 'groups;
-                            }
-                            warn!(handler = handler.name(), group, error = %e);
-                            let action = error_hook
-                                .as_ref()
-                                .map(|h| h(&bot, &ctx, e.as_ref()))
-                                .unwrap_or_default();
-                            match action {
-                                DispatcherAction::Noop => break,
-                                DispatcherAction::ContinueGroups => continue,
-                                DispatcherAction::EndGroups => break 'groups,
-                            }
-                        }
-                        Ok(Ok(())) => {
-                            debug!(handler = handler.name(), group, "ok");
-                            break;
-                        }
-                    }
+    /// Like [`Dispatcher::dispatch`], but resolves once the update has been
+    /// fully handled (or dropped).
+    pub async fn dispatch_and_wait(&self, bot: Bot, update: Update) {
+        let _ = self.submit(bot, update).await;
+    }
+
+    fn submit(&self, bot: Bot, update: Update) -> oneshot::Receiver<()> {
+        let (done, rx) = oneshot::channel();
+        let guard = self.in_flight.enter();
+        let ctx = Context::with_state(update, Arc::clone(&self.state));
+
+        let key = self.ordered_by.as_ref().and_then(|k| k.key(&ctx));
+        let Some(key) = key else {
+            let run = self.runner();
+            self.spawn(async move {
+                let _guard = guard;
+                run.run(bot, ctx).await;
+                let _ = done.send(());
+            });
+            return rx;
+        };
+
+        let job = Job {
+            bot,
+            ctx,
+            done,
+            _guard: guard,
+        };
+        let mut queues = self.queues.lock().unwrap();
+        let job = match queues.get(&key) {
+            None => Some(job),
+            Some(tx) => match tx.try_send(job) {
+                Ok(()) => None,
+                Err(TrySendError::Full(job)) => {
+                    warn!(
+                        key,
+                        update_id = job.ctx.update.update_id,
+                        "ordered queue full, update lost"
+                    );
+                    None
                 }
-            }
-        });
+                // Workers remove their queue before exiting, so this only
+                // happens if one was aborted; start a fresh worker.
+                Err(TrySendError::Closed(job)) => Some(job),
+            },
+        };
+        if let Some(job) = job {
+            let (tx, jobs) = mpsc::channel(self.queue_capacity);
+            let _ = tx.try_send(job);
+            queues.insert(key.clone(), tx);
+            self.spawn(worker(self.runner(), Arc::clone(&self.queues), key, jobs));
+        }
+        rx
+    }
+
+    fn spawn(&self, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(task).abort_handle();
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|t| !t.is_finished());
+        tasks.push(handle);
+    }
+
+    fn runner(&self) -> Runner {
+        Runner {
+            handlers: Arc::clone(&self.handlers),
+            error_hook: self.error_handler.clone(),
+            panic_hook: self.panic_handler.clone(),
+            semaphore: self.semaphore.clone(),
+            dedup: self.dedup.clone(),
+            middlewares: Arc::clone(&self.middlewares),
+        }
     }
 
-    /// Run an update in the calling task (no panic recovery; useful for tests).
+    /// Resolve once every update passed to [`Dispatcher::dispatch`] has been
+    /// fully handled.
+    pub async fn wait_idle(&self) {
+        self.in_flight.wait_idle().await;
+    }
+
+    /// Abort every task spawned for dispatched updates, e.g. after a
+    /// shutdown timeout. Queued ordered updates are dropped with them.
+    pub(crate) fn abort_all(&self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Run an update in the calling task (no panic recovery or middleware;
+    /// useful for tests).
     pub async fn process_update(&self, bot: &Bot, update: Update) {
-        let ctx = Context::new(update);
+        let ctx = Context::with_state(update, Arc::clone(&self.state));
         let snapshot: HandlerMap = self.handlers.read().unwrap().clone();
 
         'groups: for (_, handlers) in snapshot {
@@ -239,3 +390,124 @@ impl Dispatcher {
         }
     }
 }
+
+/// Handles ordered updates for one key until its queue runs dry.
+async fn worker(run: Runner, queues: Queues, key: String, mut jobs: mpsc::Receiver<Job>) {
+    loop {
+        while let Ok(job) = jobs.try_recv() {
+            run.run(job.bot, job.ctx).await;
+            let _ = job.done.send(());
+        }
+        let mut queues = queues.lock().unwrap();
+        if jobs.is_empty() {
+            queues.remove(&key);
+            return;
+        }
+    }
+}
+
+/// Everything a spawned task needs to run an update through the handlers.
+#[derive(Clone)]
+pub(crate) struct Runner {
+    handlers: Arc<RwLock<HandlerMap>>,
+    error_hook: Option<ErrorHook>,
+    panic_hook: Option<PanicHook>,
+    semaphore: Option<Arc<Semaphore>>,
+    dedup: Option<Arc<dyn DedupStore>>,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+}
+
+impl Runner {
+    async fn run(&self, bot: Bot, ctx: Context) {
+        if let Some(store) = &self.dedup {
+            if !dedup::is_new(store.as_ref(), bot.me.id, ctx.update.update_id).await {
+                return;
+            }
+        }
+
+        let _permit = if let Some(sem) = &self.semaphore {
+            Some(sem.clone().acquire_owned().await.ok())
+        } else {
+            None
+        };
+
+        Next::new(&self.middlewares, self).run(bot, ctx).await;
+    }
+
+    /// Iterate the handler groups; the innermost step of the middleware chain.
+    pub(crate) async fn run_groups(&self, bot: Bot, ctx: Context) -> DispatchOutcome {
+        let mut outcome = DispatchOutcome::default();
+
+        // Snapshot the entire handler map once — single read lock, no per-group re-lock.
+        let snapshot: HandlerMap = self.handlers.read().unwrap().clone();
+
+        'groups: for (group, handlers) in snapshot {
+            for handler in handlers {
+                if !handler.check_update(&ctx) {
+                    continue;
+                }
+
+                debug!(handler = handler.name(), group, "matched");
+                outcome.handlers.push((group, handler.name().to_string()));
+
+                let h = Arc::clone(&handler);
+                let bot2 = bot.clone();
+                let ctx2 = ctx.clone();
+                // Unlike a bare JoinHandle, the set aborts the handler when
+                // this task is aborted, e.g. on shutdown.
+                let mut join = JoinSet::new();
+                join.spawn(async move { h.handle_update(bot2, ctx2).await });
+
+                match join.join_next().await.expect("handler task spawned") {
+                    Err(e) if e.is_panic() => {
+                        let msg = e
+                            .into_panic()
+                            .downcast::<String>()
+                            .map(|s| *s)
+                            .or_else(|p| p.downcast::<&str>().map(|s| s.to_string()))
+                            .unwrap_or_else(|_| "<non-string panic>".into());
+                        outcome.errors.push(msg.clone());
+                        if let Some(hook) = &self.panic_hook {
+                            hook(&bot, &ctx, msg);
+                        } else {
+                            error!(handler = handler.name(), group, panic = %msg, "panicked");
+                        }
+                        break;
+                    }
+                    Err(e) => {
+                        error!(handler = handler.name(), group, error = %e, "task aborted");
+                        outcome.errors.push(e.to_string());
+                        break;
+                    }
+                    Ok(Err(e)) => {
+                        if e.is::<ContinueGroups>() {
+                            debug!(handler = handler.name(), "ContinueGroups");
+                            continue;
+                        }
+                        if e.is::<EndGroups>() {
+                            debug!(handler = handler.name(), "EndGroups");
+                            break 'groups;
+                        }
+                        warn!(handler = handler.name(), group, error = %e);
+                        outcome.errors.push(e.to_string());
+                        let action = self
+                            .error_hook
+                            .as_ref()
+                            .map(|h| h(&bot, &ctx, e.as_ref()))
+                            .unwrap_or_default();
+                        match action {
+                            DispatcherAction::Noop => break,
+                            DispatcherAction::ContinueGroups => continue,
+                            DispatcherAction::EndGroups => break 'groups,
+                        }
+                    }
+                    Ok(Ok(())) => {
+                        debug!(handler = handler.name(), group, "ok");
+                        break;
+                    }
+                }
+            }
+        }
+        outcome
+    }
+}
@@ -0,0 +1,78 @@
+//! Typed values keyed by their type, for application state and per-update
+//! data.
+//!
+//! [`DispatcherOpts::state`](crate::DispatcherOpts::state) stores values
+//! every handler can read with [`Context::state`](crate::Context::state),
+//! e.g. a database pool or the configuration. Middleware can attach values to
+//! a single update through [`Context::extensions`](crate::Context), read
+//! with [`Context::extension`](crate::Context::extension).
+//!
+//! ```rust
+//! use tgbotrs::{Context, DispatcherOpts};
+//!
+//! struct Config {
+//!     admin_id: i64,
+//! }
+//!
+//! let opts = DispatcherOpts::default().state(Config { admin_id: 42 });
+//!
+//! fn is_admin(ctx: &Context) -> bool {
+//!     let admin_id = ctx.state::<Config>().map(|c| c.admin_id);
+//!     ctx.effective_user().map(|u| u.id) == admin_id
+//! }
+//! ```
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+/// A map holding at most one value per type.
+///
+/// Values are reference counted, so cloning is cheap.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `value`, replacing any earlier value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove the value of type `T`; returns whether there was one.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -52,6 +52,10 @@ pub fn venue() -> impl super::Filter<Message> {
 pub fn animation() -> impl super::Filter<Message> {
     |m: &Message| m.animation.is_some()
 }
+/// Group-to-supergroup migration service messages.
+pub fn migrate() -> impl super::Filter<Message> {
+    |m: &Message| m.migrate_to_chat_id.is_some() || m.migrate_from_chat_id.is_some()
+}
 pub fn reply() -> impl super::Filter<Message> {
     |m: &Message| m.reply_to_message.is_some()
 }
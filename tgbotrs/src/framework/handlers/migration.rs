@@ -0,0 +1,78 @@
+use std::{future::Future, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{
+    framework::{
+        context::Context,
+        handler::{Handler, HandlerResult},
+    },
+    Bot,
+};
+
+type Fn = Arc<
+    dyn std::ops::Fn(Bot, Context) -> std::pin::Pin<Box<dyn Future<Output = HandlerResult> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Fires on group-to-supergroup migration service messages
+/// (`migrate_to_chat_id` / `migrate_from_chat_id`).
+///
+/// Use [`Message::chat_migration`](crate::types::Message::chat_migration) in
+/// the callback to get `(old_chat_id, new_chat_id)`. Telegram sends one
+/// message to each chat, so the callback usually runs twice per migration.
+///
+/// ```rust,no_run
+/// use tgbotrs::framework::{handlers::ChatMigrationHandler, Context, HandlerResult};
+/// use tgbotrs::Bot;
+///
+/// async fn migrated(_bot: Bot, ctx: Context) -> HandlerResult {
+///     if let Some((old, new)) = ctx.effective_message().and_then(|m| m.chat_migration()) {
+///         println!("chat {old} is now {new}");
+///     }
+///     Ok(())
+/// }
+///
+/// let handler = ChatMigrationHandler::new(migrated);
+/// ```
+pub struct ChatMigrationHandler {
+    name: String,
+    func: Fn,
+}
+
+impl ChatMigrationHandler {
+    pub fn new<Fut>(func: impl std::ops::Fn(Bot, Context) -> Fut + Send + Sync + 'static) -> Self
+    where
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        Self {
+            name: "chat_migration".into(),
+            func: Arc::new(move |bot, ctx| Box::pin(func(bot, ctx))),
+        }
+    }
+
+    /// Override the default name (`"chat_migration"`).
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Handler for ChatMigrationHandler {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check_update(&self, ctx: &Context) -> bool {
+        ctx.update
+            .message
+            .as_ref()
+            .is_some_and(|m| m.chat_migration().is_some())
+    }
+
+    async fn handle_update(&self, bot: Bot, ctx: Context) -> HandlerResult {
+        (self.func)(bot, ctx).await
+    }
+}
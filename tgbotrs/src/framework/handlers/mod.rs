@@ -2,6 +2,7 @@ pub mod callback_query;
 pub mod command;
 pub mod conversation;
 pub mod message;
+pub mod migration;
 
 pub use callback_query::CallbackQueryHandler;
 pub use command::CommandHandler;
@@ -9,3 +10,4 @@ pub use conversation::{
     ConversationHandler, ConversationOpts, EndConversation, InMemoryStorage, KeyStrategy, NextState,
 };
 pub use message::MessageHandler;
+pub use migration::ChatMigrationHandler;
@@ -0,0 +1,96 @@
+//! Middleware - code that wraps every update the dispatcher handles.
+//!
+//! Middlewares run in the order they were added, the first one outermost.
+//! Each receives the update's [`Context`] and a [`Next`] for the rest of the
+//! chain, ending in the handler groups. A middleware may change the context
+//! before calling [`Next::run`], e.g. attach typed values to
+//! `ctx.extensions` for handlers to read with [`Context::extension`], return
+//! without calling it to skip the update, and inspect the returned
+//! [`DispatchOutcome`] afterwards.
+//!
+//! ```rust
+//! use async_trait::async_trait;
+//! use tgbotrs::{Bot, Context, DispatchOutcome, DispatcherOpts, Middleware, Next};
+//!
+//! /// Only lets the admin through.
+//! struct AdminOnly(i64);
+//!
+//! #[async_trait]
+//! impl Middleware for AdminOnly {
+//!     async fn handle(&self, bot: Bot, ctx: Context, next: Next<'_>) -> DispatchOutcome {
+//!         if ctx.effective_user().map(|u| u.id) != Some(self.0) {
+//!             return DispatchOutcome::skipped();
+//!         }
+//!         next.run(bot, ctx).await
+//!     }
+//! }
+//!
+//! let opts = DispatcherOpts::default().middleware(AdminOnly(12345));
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    framework::{context::Context, dispatcher::Runner},
+    Bot,
+};
+
+/// Wraps the handling of every update; see the [module docs](self).
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Handle `ctx`, usually by calling `next.run(bot, ctx)`.
+    async fn handle(&self, bot: Bot, ctx: Context, next: Next<'_>) -> DispatchOutcome;
+}
+
+/// The rest of the middleware chain, ending in the handler groups.
+pub struct Next<'a> {
+    chain: &'a [Arc<dyn Middleware>],
+    runner: &'a Runner,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(chain: &'a [Arc<dyn Middleware>], runner: &'a Runner) -> Self {
+        Self { chain, runner }
+    }
+
+    /// Pass the update on to the next middleware, or the handlers.
+    pub async fn run(self, bot: Bot, ctx: Context) -> DispatchOutcome {
+        match self.chain.split_first() {
+            Some((first, rest)) => first.handle(bot, ctx, Next::new(rest, self.runner)).await,
+            None => self.runner.run_groups(bot, ctx).await,
+        }
+    }
+}
+
+/// What happened to an update, as returned by [`Next::run`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DispatchOutcome {
+    /// Handlers that ran, in order, as `(group, name)`.
+    pub handlers: Vec<(i32, String)>,
+    /// Errors returned by those handlers, and panic messages.
+    pub errors: Vec<String>,
+    /// A middleware skipped the update.
+    pub skipped: bool,
+}
+
+impl DispatchOutcome {
+    /// The outcome of a middleware not calling [`Next::run`].
+    pub fn skipped() -> Self {
+        Self {
+            skipped: true,
+            ..Self::default()
+        }
+    }
+
+    /// At least one handler ran.
+    pub fn is_handled(&self) -> bool {
+        !self.handlers.is_empty()
+    }
+
+    /// No handler failed or panicked.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
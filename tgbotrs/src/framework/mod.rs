@@ -1,14 +1,20 @@
 pub mod context;
 pub mod dispatcher;
+pub mod extensions;
 pub mod filters;
 pub mod handler;
 pub mod handlers;
+pub mod middleware;
 
 pub use context::Context;
-pub use dispatcher::{Dispatcher, DispatcherAction, DispatcherOpts, ErrorHook, PanicHook};
+pub use dispatcher::{
+    Dispatcher, DispatcherAction, DispatcherOpts, ErrorHook, PanicHook, UpdateKey, UpdateKeyFn,
+};
+pub use extensions::Extensions;
 pub use filters::FilterExt;
 pub use handler::{ContinueGroups, EndGroups, Handler, HandlerResult};
 pub use handlers::{
-    CallbackQueryHandler, CommandHandler, ConversationHandler, ConversationOpts, EndConversation,
-    InMemoryStorage, KeyStrategy, MessageHandler, NextState,
+    CallbackQueryHandler, ChatMigrationHandler, CommandHandler, ConversationHandler,
+    ConversationOpts, EndConversation, InMemoryStorage, KeyStrategy, MessageHandler, NextState,
 };
+pub use middleware::{DispatchOutcome, Middleware, Next};
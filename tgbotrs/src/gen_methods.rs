@@ -23,6 +23,7 @@ impl Bot {
         sticker: InputSticker,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "user_id".into(),
             serde_json::to_value(user_id).unwrap_or_default(),
@@ -33,9 +34,9 @@ impl Bot {
         );
         req.insert(
             "sticker".into(),
-            serde_json::to_value(sticker).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&sticker, &mut files)?,
         );
-        self.call_api("addStickerToSet", serde_json::Value::Object(req))
+        self.call_api_with_attachments("addStickerToSet", req, files)
             .await
     }
 }
@@ -1158,6 +1159,7 @@ impl Bot {
         params: Option<CreateNewStickerSetParams>,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "user_id".into(),
             serde_json::to_value(user_id).unwrap_or_default(),
@@ -1172,10 +1174,10 @@ impl Bot {
         );
         req.insert(
             "stickers".into(),
-            serde_json::to_value(stickers).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&stickers, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -1184,7 +1186,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("createNewStickerSet", serde_json::Value::Object(req))
+        self.call_api_with_attachments("createNewStickerSet", req, files)
             .await
     }
 }
@@ -2055,16 +2057,17 @@ impl Bot {
     /// See: https://core.telegram.org/bots/api#editmessagemedia
     pub async fn edit_message_media(
         &self,
-        media: Vec<InputMedia>,
+        media: InputMedia,
         params: Option<EditMessageMediaParams>,
     ) -> Result<serde_json::Value, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "media".into(),
-            serde_json::to_value(&media).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&media, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -2073,7 +2076,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("editMessageMedia", serde_json::Value::Object(req))
+        self.call_api_with_attachments("editMessageMedia", req, files)
             .await
     }
 }
@@ -2292,6 +2295,7 @@ impl Bot {
         params: Option<EditStoryParams>,
     ) -> Result<Story, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "business_connection_id".into(),
             serde_json::to_value(business_connection_id.into()).unwrap_or_default(),
@@ -2302,10 +2306,10 @@ impl Bot {
         );
         req.insert(
             "content".into(),
-            serde_json::to_value(content).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&content, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -2314,7 +2318,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("editStory", serde_json::Value::Object(req))
+        self.call_api_with_attachments("editStory", req, files)
             .await
     }
 }
@@ -3848,20 +3852,21 @@ impl Bot {
         params: Option<PostStoryParams>,
     ) -> Result<Story, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "business_connection_id".into(),
             serde_json::to_value(business_connection_id.into()).unwrap_or_default(),
         );
         req.insert(
             "content".into(),
-            serde_json::to_value(content).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&content, &mut files)?,
         );
         req.insert(
             "active_period".into(),
             serde_json::to_value(active_period).unwrap_or_default(),
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -3870,7 +3875,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("postStory", serde_json::Value::Object(req))
+        self.call_api_with_attachments("postStory", req, files)
             .await
     }
 }
@@ -4241,6 +4246,7 @@ impl Bot {
         sticker: InputSticker,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "user_id".into(),
             serde_json::to_value(user_id).unwrap_or_default(),
@@ -4255,9 +4261,9 @@ impl Bot {
         );
         req.insert(
             "sticker".into(),
-            serde_json::to_value(sticker).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&sticker, &mut files)?,
         );
-        self.call_api("replaceStickerInSet", serde_json::Value::Object(req))
+        self.call_api_with_attachments("replaceStickerInSet", req, files)
             .await
     }
 }
@@ -4663,12 +4669,20 @@ impl Bot {
         params: Option<SendAnimationParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "animation".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(
+                &animation.into(),
+                &mut files,
+            )?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -4677,7 +4691,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendAnimation", req, "animation", animation.into())
+        self.call_api_with_attachments("sendAnimation", req, files)
             .await
     }
 }
@@ -4823,12 +4837,17 @@ impl Bot {
         params: Option<SendAudioParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "audio".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(&audio.into(), &mut files)?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -4837,7 +4856,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendAudio", req, "audio", audio.into())
+        self.call_api_with_attachments("sendAudio", req, files)
             .await
     }
 }
@@ -5360,12 +5379,20 @@ impl Bot {
         params: Option<SendDocumentParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "document".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(
+                &document.into(),
+                &mut files,
+            )?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -5374,7 +5401,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendDocument", req, "document", document.into())
+        self.call_api_with_attachments("sendDocument", req, files)
             .await
     }
 }
@@ -6023,16 +6050,17 @@ impl Bot {
         params: Option<SendMediaGroupParams>,
     ) -> Result<Vec<Message>, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
         req.insert(
             "media".into(),
-            serde_json::to_value(&media).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&media, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -6041,7 +6069,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("sendMediaGroup", serde_json::Value::Object(req))
+        self.call_api_with_attachments("sendMediaGroup", req, files)
             .await
     }
 }
@@ -6371,6 +6399,7 @@ impl Bot {
         params: Option<SendPaidMediaParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
@@ -6381,10 +6410,10 @@ impl Bot {
         );
         req.insert(
             "media".into(),
-            serde_json::to_value(media).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&media, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -6393,7 +6422,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("sendPaidMedia", serde_json::Value::Object(req))
+        self.call_api_with_attachments("sendPaidMedia", req, files)
             .await
     }
 }
@@ -6524,12 +6553,17 @@ impl Bot {
         params: Option<SendPhotoParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "photo".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(&photo.into(), &mut files)?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -6538,7 +6572,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendPhoto", req, "photo", photo.into())
+        self.call_api_with_attachments("sendPhoto", req, files)
             .await
     }
 }
@@ -6880,12 +6914,20 @@ impl Bot {
         params: Option<SendStickerParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "sticker".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(
+                &sticker.into(),
+                &mut files,
+            )?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -6894,7 +6936,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendSticker", req, "sticker", sticker.into())
+        self.call_api_with_attachments("sendSticker", req, files)
             .await
     }
 }
@@ -7231,12 +7273,17 @@ impl Bot {
         params: Option<SendVideoParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "video".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(&video.into(), &mut files)?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -7245,7 +7292,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendVideo", req, "video", video.into())
+        self.call_api_with_attachments("sendVideo", req, files)
             .await
     }
 }
@@ -7362,12 +7409,20 @@ impl Bot {
         params: Option<SendVideoNoteParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "video_note".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(
+                &video_note.into(),
+                &mut files,
+            )?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -7376,7 +7431,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendVideoNote", req, "video_note", video_note.into())
+        self.call_api_with_attachments("sendVideoNote", req, files)
             .await
     }
 }
@@ -7500,12 +7555,17 @@ impl Bot {
         params: Option<SendVoiceParams>,
     ) -> Result<Message, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
+        req.insert(
+            "voice".into(),
+            crate::input_file::to_value_collecting::<InputFileOrString>(&voice.into(), &mut files)?,
+        );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -7514,7 +7574,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api_with_file("sendVoice", req, "voice", voice.into())
+        self.call_api_with_attachments("sendVoice", req, files)
             .await
     }
 }
@@ -7674,16 +7734,17 @@ impl Bot {
         params: Option<SetBusinessAccountProfilePhotoParams>,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "business_connection_id".into(),
             serde_json::to_value(business_connection_id.into()).unwrap_or_default(),
         );
         req.insert(
             "photo".into(),
-            serde_json::to_value(photo).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&photo, &mut files)?,
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -7692,11 +7753,8 @@ impl Bot {
                 }
             }
         }
-        self.call_api(
-            "setBusinessAccountProfilePhoto",
-            serde_json::Value::Object(req),
-        )
-        .await
+        self.call_api_with_attachments("setBusinessAccountProfilePhoto", req, files)
+            .await
     }
 }
 
@@ -7981,15 +8039,16 @@ impl Bot {
         photo: InputFile,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "chat_id".into(),
             serde_json::to_value(chat_id.into()).unwrap_or_default(),
         );
         req.insert(
             "photo".into(),
-            serde_json::to_value(photo).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&photo, &mut files)?,
         );
-        self.call_api("setChatPhoto", serde_json::Value::Object(req))
+        self.call_api_with_attachments("setChatPhoto", req, files)
             .await
     }
 }
@@ -8426,11 +8485,12 @@ impl Bot {
     /// See: https://core.telegram.org/bots/api#setmyprofilephoto
     pub async fn set_my_profile_photo(&self, photo: InputProfilePhoto) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "photo".into(),
-            serde_json::to_value(photo).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&photo, &mut files)?,
         );
-        self.call_api("setMyProfilePhoto", serde_json::Value::Object(req))
+        self.call_api_with_attachments("setMyProfilePhoto", req, files)
             .await
     }
 }
@@ -8671,6 +8731,7 @@ impl Bot {
         params: Option<SetStickerSetThumbnailParams>,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "name".into(),
             serde_json::to_value(name.into()).unwrap_or_default(),
@@ -8684,7 +8745,7 @@ impl Bot {
             serde_json::to_value(format.into()).unwrap_or_default(),
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -8693,7 +8754,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("setStickerSetThumbnail", serde_json::Value::Object(req))
+        self.call_api_with_attachments("setStickerSetThumbnail", req, files)
             .await
     }
 }
@@ -8836,12 +8897,13 @@ impl Bot {
         params: Option<SetWebhookParams>,
     ) -> Result<bool, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "url".into(),
             serde_json::to_value(url.into()).unwrap_or_default(),
         );
         if let Some(p) = params {
-            let extra = serde_json::to_value(&p).unwrap_or_default();
+            let extra = crate::input_file::to_value_collecting(&p, &mut files)?;
             if let serde_json::Value::Object(m) = extra {
                 for (k, v) in m {
                     if !v.is_null() {
@@ -8850,7 +8912,7 @@ impl Bot {
                 }
             }
         }
-        self.call_api("setWebhook", serde_json::Value::Object(req))
+        self.call_api_with_attachments("setWebhook", req, files)
             .await
     }
 }
@@ -9400,19 +9462,20 @@ impl Bot {
         sticker_format: impl Into<String>,
     ) -> Result<File, BotError> {
         let mut req = serde_json::Map::new();
+        let mut files = Vec::new();
         req.insert(
             "user_id".into(),
             serde_json::to_value(user_id).unwrap_or_default(),
         );
         req.insert(
             "sticker".into(),
-            serde_json::to_value(sticker).unwrap_or_default(),
+            crate::input_file::to_value_collecting(&sticker, &mut files)?,
         );
         req.insert(
             "sticker_format".into(),
             serde_json::to_value(sticker_format.into()).unwrap_or_default(),
         );
-        self.call_api("uploadStickerFile", serde_json::Value::Object(req))
+        self.call_api_with_attachments("uploadStickerFile", req, files)
             .await
     }
 }
@@ -80,6 +80,20 @@ impl Message {
         }
     }
 
+    /// For a group-to-supergroup migration service message, returns
+    /// `(old_chat_id, new_chat_id)`.
+    ///
+    /// Telegram sends two such messages: `migrate_to_chat_id` in the old
+    /// group and `migrate_from_chat_id` in the new supergroup. Both yield the
+    /// same pair.
+    pub fn chat_migration(&self) -> Option<(i64, i64)> {
+        match (self.migrate_to_chat_id, self.migrate_from_chat_id) {
+            (Some(to), _) => Some((self.chat.id, to)),
+            (None, Some(from)) => Some((from, self.chat.id)),
+            (None, None) => None,
+        }
+    }
+
     /// Send a reply to this message in the same chat.
     ///
     /// `reply_parameters` is set automatically if not already present in
@@ -144,7 +158,7 @@ impl File {
     /// Returns the HTTPS URL to download this file from Telegram's CDN.
     ///
     /// Returns `None` when `file_path` is absent. Call `bot.get_file(file_id)`
-    /// first to populate it.
+    /// first to populate it. Use [`Bot::download_file`] to fetch the contents.
     ///
     /// # Example
     /// ```rust,no_run
@@ -157,9 +171,7 @@ impl File {
     /// # }
     /// ```
     pub fn url(&self, bot: &Bot) -> Option<String> {
-        self.file_path
-            .as_ref()
-            .map(|path| format!("{}/file/bot{}/{}", bot.api_url, bot.token, path))
+        self.file_path.as_ref().map(|path| bot.file_url(path))
     }
 }
 
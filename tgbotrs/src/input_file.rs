@@ -1,16 +1,59 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
 use bytes::Bytes;
 use serde::{Deserialize, Serialize, Serializer};
+use tokio::io::AsyncRead;
+
+use crate::{
+    client::{FormBody, FormPart},
+    BotError,
+};
+
+pub(crate) fn infer_mime(filename: &str) -> String {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "pdf" => "application/pdf",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+    .to_string()
+}
 
 /// A file to be sent via the Telegram Bot API.
 ///
 /// - `FileId` - reference a file already on Telegram's servers
 /// - `Url` - let Telegram download the file from a URL
 /// - `Memory` - upload raw bytes directly via multipart
-#[derive(Debug, Clone)]
+/// - `Path` - stream a file from disk via multipart, without loading it into memory
+/// - `Stream` - stream any [`AsyncRead`] via multipart; `len` is sent as the
+///   part length when known
+#[derive(Debug, Clone, PartialEq)]
 pub enum InputFile {
     FileId(String),
     Url(String),
-    Memory { filename: String, data: Bytes },
+    Memory {
+        filename: String,
+        data: Bytes,
+    },
+    Path(PathBuf),
+    Stream {
+        filename: String,
+        reader: ReaderHandle,
+        len: Option<u64>,
+    },
 }
 
 impl InputFile {
@@ -28,22 +71,170 @@ impl InputFile {
             data: data.into(),
         }
     }
-}
 
-// Memory files are handled separately when building multipart requests.
-// FileId and Url serialize to their string value.
-impl Serialize for InputFile {
-    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    /// A file on disk. It is opened and streamed when the request is sent.
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        InputFile::Path(path.into())
+    }
+
+    /// An async reader streamed when the request is sent. It can be uploaded
+    /// once; clones share the same reader.
+    pub fn stream(
+        filename: impl Into<String>,
+        reader: impl AsyncRead + Send + 'static,
+        len: Option<u64>,
+    ) -> Self {
+        InputFile::Stream {
+            filename: filename.into(),
+            reader: ReaderHandle::new(reader),
+            len,
+        }
+    }
+
+    /// Form body for a file uploaded with the request, `None` for file IDs and URLs.
+    pub(crate) fn to_form_body(&self) -> Result<Option<FormBody>, BotError> {
+        let body = match self {
+            InputFile::FileId(_) | InputFile::Url(_) => return Ok(None),
+            InputFile::Memory { filename, data } => FormBody::Bytes {
+                filename: filename.clone(),
+                mime: infer_mime(filename),
+                data: data.clone(),
+            },
+            InputFile::Path(path) => {
+                let filename = path_file_name(path);
+                FormBody::Path {
+                    mime: infer_mime(&filename),
+                    filename,
+                    path: path.clone(),
+                }
+            }
+            InputFile::Stream {
+                filename,
+                reader,
+                len,
+            } => FormBody::Stream {
+                filename: filename.clone(),
+                mime: infer_mime(filename),
+                reader: reader.take().ok_or_else(|| {
+                    BotError::Other(format!("stream {} was already uploaded", filename))
+                })?,
+                len: *len,
+            },
+        };
+        Ok(Some(body))
+    }
+
+    /// File name used in the `attach://` reference outside a collecting context.
+    fn attach_name(&self) -> Option<String> {
         match self {
-            InputFile::FileId(id) => serializer.serialize_str(id),
-            InputFile::Url(url) => serializer.serialize_str(url),
-            InputFile::Memory { filename, .. } => {
-                serializer.serialize_str(&format!("attach://{}", filename))
+            InputFile::FileId(_) | InputFile::Url(_) => None,
+            InputFile::Memory { filename, .. } | InputFile::Stream { filename, .. } => {
+                Some(filename.clone())
             }
+            InputFile::Path(path) => Some(path_file_name(path)),
         }
     }
 }
 
+fn path_file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string())
+}
+
+/// A boxed [`AsyncRead`] that can be taken exactly once.
+///
+/// Lets [`InputFile`] stay `Clone`: clones share the reader, and whichever
+/// request uploads it first consumes it.
+#[derive(Clone)]
+pub struct ReaderHandle(Arc<Mutex<Option<BoxedReader>>>);
+
+/// The reader type carried by [`FormBody::Stream`].
+pub type BoxedReader = Pin<Box<dyn AsyncRead + Send>>;
+
+impl ReaderHandle {
+    pub fn new(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(Box::pin(reader)))))
+    }
+
+    /// Take the reader, leaving the handle empty. `None` if already taken.
+    pub fn take(&self) -> Option<BoxedReader> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+impl fmt::Debug for ReaderHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let consumed = self.0.lock().map(|r| r.is_none()).unwrap_or(true);
+        f.debug_struct("ReaderHandle")
+            .field("consumed", &consumed)
+            .finish()
+    }
+}
+
+impl PartialEq for ReaderHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Attachments collected while serializing a request body.
+//
+// Serialization is synchronous, so a thread-local is enough to hand the
+// uploadable files found anywhere in the value tree back to the caller.
+thread_local! {
+    static ATTACHMENTS: RefCell<Option<Vec<FormPart>>> = const { RefCell::new(None) };
+}
+
+/// Serialize `value`, moving every uploadable `InputFile` inside it into `parts`.
+///
+/// Each file is referenced as `attach://fileN` in the returned JSON, where `N`
+/// is its index in `parts`, so names stay unique across calls sharing `parts`.
+///
+/// Fails if a file can't be attached, e.g. a stream that was already
+/// uploaded; the request must not be sent without it.
+pub(crate) fn to_value_collecting<T: Serialize + ?Sized>(
+    value: &T,
+    parts: &mut Vec<FormPart>,
+) -> Result<serde_json::Value, BotError> {
+    ATTACHMENTS.with(|c| *c.borrow_mut() = Some(std::mem::take(parts)));
+    let out = serde_json::to_value(value);
+    *parts = ATTACHMENTS
+        .with(|c| c.borrow_mut().take())
+        .unwrap_or_default();
+    Ok(out?)
+}
+
+// FileId and Url serialize to their string value. Uploadable files serialize
+// to an `attach://` reference; inside `to_value_collecting` the file is
+// attached under a unique name, otherwise the file name is used as-is.
+impl Serialize for InputFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = match self {
+            InputFile::FileId(id) => return serializer.serialize_str(id),
+            InputFile::Url(url) => return serializer.serialize_str(url),
+            _ => self.attach_name().unwrap_or_default(),
+        };
+        let attached = ATTACHMENTS.with(|c| {
+            c.borrow_mut()
+                .as_mut()
+                .map(|parts| -> Result<String, BotError> {
+                    let name = format!("file{}", parts.len());
+                    if let Some(body) = self.to_form_body()? {
+                        parts.push(FormPart {
+                            name: name.clone(),
+                            body,
+                        });
+                    }
+                    Ok(name)
+                })
+                .transpose()
+        });
+        let name = attached.map_err(serde::ser::Error::custom)?.unwrap_or(name);
+        serializer.serialize_str(&format!("attach://{}", name))
+    }
+}
+
 impl<'de> Deserialize<'de> for InputFile {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let s = String::deserialize(deserializer)?;
@@ -67,13 +258,19 @@ impl From<&str> for InputFile {
 }
 
 /// A field that accepts either an [`InputFile`] or a plain string (file_id / URL).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum InputFileOrString {
     File(InputFile),
     String(String),
 }
 
+impl Default for InputFileOrString {
+    fn default() -> Self {
+        InputFileOrString::String(String::new())
+    }
+}
+
 impl From<InputFile> for InputFileOrString {
     fn from(f: InputFile) -> Self {
         InputFileOrString::File(f)
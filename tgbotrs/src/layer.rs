@@ -0,0 +1,435 @@
+//! Composable middleware around [`BotClient`].
+//!
+//! A [`BotClientLayer`] takes a client and returns a new one that wraps it,
+//! in the spirit of `tower::Layer`. Stack layers on a bot with
+//! [`Bot::with_layer`](crate::Bot::with_layer) or
+//! [`Bot::with_layers`](crate::Bot::with_layers); the transport underneath
+//! (the default [`ReqwestClient`](crate::ReqwestClient) or your own) is left
+//! untouched.
+//!
+//! Ready-made layers:
+//!
+//! - [`LoggingLayer`]: one `tracing` event per request, without the token
+//! - [`TimingLayer`]: reports how long each request took
+//! - [`HeaderLayer`]: adds HTTP headers to every request
+//! - [`TimeoutLayer`]: per-method deadlines
+//! - [`RateLimit`](crate::RateLimit): Telegram's sending limits
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tgbotrs::{
+//!     layer::{BotClientLayer, HeaderLayer, LoggingLayer, TimeoutLayer, TimingLayer},
+//!     Bot,
+//! };
+//!
+//! # async fn example() -> Result<(), tgbotrs::BotError> {
+//! let layers: Vec<Box<dyn BotClientLayer>> = vec![
+//!     Box::new(LoggingLayer),
+//!     Box::new(TimingLayer::new(|method, elapsed, _ok| {
+//!         println!("{method} took {elapsed:?}");
+//!     })),
+//!     Box::new(HeaderLayer::new().header("X-Request-Source", "billing")),
+//!     Box::new(
+//!         TimeoutLayer::new()
+//!             .default_timeout(Duration::from_secs(10))
+//!             .method("getUpdates", Duration::from_secs(40)),
+//!     ),
+//! ];
+//! let bot = Bot::new("YOUR_TOKEN").await?.with_layers(layers);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Writing a layer
+//!
+//! Implement [`BotClientLayer`] and return a client that forwards to `inner`:
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use async_trait::async_trait;
+//! use tgbotrs::{layer::BotClientLayer, BotClient, BotError, FormPart};
+//!
+//! #[derive(Debug)]
+//! struct Counting(Arc<dyn BotClient>);
+//!
+//! #[async_trait]
+//! impl BotClient for Counting {
+//!     async fn post_json(&self, url: &str, body: serde_json::Value) -> Result<bytes::Bytes, BotError> {
+//!         // count here...
+//!         self.0.post_json(url, body).await
+//!     }
+//!     async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+//!         self.0.post_form(url, parts).await
+//!     }
+//! }
+//!
+//! struct CountingLayer;
+//!
+//! impl BotClientLayer for CountingLayer {
+//!     fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+//!         Arc::new(Counting(inner))
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tracing::{debug, warn};
+
+use crate::{
+    client::{method_name, BotClient, FormPart, ResponseBody},
+    rate_limit::{RateLimit, RateLimitedClient},
+    BotError,
+};
+
+/// Wraps a [`BotClient`] in another one.
+pub trait BotClientLayer: Send + Sync {
+    /// Return a client that handles requests and forwards them to `inner`.
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient>;
+}
+
+impl<L: BotClientLayer + ?Sized> BotClientLayer for Box<L> {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        (**self).layer(inner)
+    }
+}
+
+impl BotClientLayer for RateLimit {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        Arc::new(RateLimitedClient::new(inner, self.clone()))
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_HEADERS: Vec<(String, String)>;
+}
+
+/// Headers that [`HeaderLayer`]s above the current request asked for.
+///
+/// [`ReqwestClient`](crate::ReqwestClient) applies them automatically; custom
+/// transports can call this from their `post_json`/`post_form` to do the same.
+pub fn request_headers() -> Vec<(String, String)> {
+    REQUEST_HEADERS.try_with(|h| h.clone()).unwrap_or_default()
+}
+
+// Shared plumbing: most layers only need to run code around the inner call.
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+trait Around: Send + Sync + fmt::Debug {
+    fn around<'a, T: Send + 'a>(
+        &'a self,
+        method: &'a str,
+        call: BoxFuture<'a, Result<T, BotError>>,
+    ) -> BoxFuture<'a, Result<T, BotError>>;
+}
+
+#[derive(Debug)]
+struct Wrapped<A> {
+    inner: Arc<dyn BotClient>,
+    hook: A,
+}
+
+/// Label used for file downloads, whose URLs end in a file path.
+const DOWNLOAD: &str = "download";
+
+#[async_trait]
+impl<A: Around> BotClient for Wrapped<A> {
+    async fn post_json(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<bytes::Bytes, BotError> {
+        let call = self.inner.post_json(url, body);
+        self.hook.around(method_name(url), call).await
+    }
+
+    async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+        let call = self.inner.post_form(url, parts);
+        self.hook.around(method_name(url), call).await
+    }
+
+    async fn get(&self, url: &str) -> Result<bytes::Bytes, BotError> {
+        self.hook.around(DOWNLOAD, self.inner.get(url)).await
+    }
+
+    async fn get_stream(&self, url: &str) -> Result<ResponseBody, BotError> {
+        self.hook.around(DOWNLOAD, self.inner.get_stream(url)).await
+    }
+}
+
+fn wrap<A: Around + 'static>(inner: Arc<dyn BotClient>, hook: A) -> Arc<dyn BotClient> {
+    Arc::new(Wrapped { inner, hook })
+}
+
+// Logging
+/// Emits a `debug` event for every request and a `warn` event for transport
+/// failures. Only the method name is logged, never the URL or token.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingLayer;
+
+impl BotClientLayer for LoggingLayer {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        wrap(inner, LoggingLayer)
+    }
+}
+
+impl Around for LoggingLayer {
+    fn around<'a, T: Send + 'a>(
+        &'a self,
+        method: &'a str,
+        call: BoxFuture<'a, Result<T, BotError>>,
+    ) -> BoxFuture<'a, Result<T, BotError>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let res = call.await;
+            let elapsed = start.elapsed();
+            match &res {
+                Ok(_) => debug!(method, ?elapsed, "request completed"),
+                Err(e) => warn!(method, ?elapsed, error = %e, "request failed"),
+            }
+            res
+        })
+    }
+}
+
+// Timing
+type TimingFn = dyn Fn(&str, Duration, bool) + Send + Sync;
+
+/// Calls `f(method, elapsed, ok)` after every request, e.g. to feed a
+/// metrics histogram. `ok` is `false` only for transport failures; Telegram
+/// API errors arrive as successful responses at this level.
+#[derive(Clone)]
+pub struct TimingLayer {
+    f: Arc<TimingFn>,
+}
+
+impl TimingLayer {
+    pub fn new(f: impl Fn(&str, Duration, bool) + Send + Sync + 'static) -> Self {
+        Self { f: Arc::new(f) }
+    }
+}
+
+impl fmt::Debug for TimingLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimingLayer").finish_non_exhaustive()
+    }
+}
+
+impl BotClientLayer for TimingLayer {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        wrap(inner, self.clone())
+    }
+}
+
+impl Around for TimingLayer {
+    fn around<'a, T: Send + 'a>(
+        &'a self,
+        method: &'a str,
+        call: BoxFuture<'a, Result<T, BotError>>,
+    ) -> BoxFuture<'a, Result<T, BotError>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let res = call.await;
+            (self.f)(method, start.elapsed(), res.is_ok());
+            res
+        })
+    }
+}
+
+// Headers
+/// Adds HTTP headers to every request, e.g. for an authenticating proxy in
+/// front of a local Bot API server.
+///
+/// Headers are passed down to the transport through [`request_headers`].
+#[derive(Debug, Clone, Default)]
+pub struct HeaderLayer {
+    headers: Vec<(String, String)>,
+}
+
+impl HeaderLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+impl BotClientLayer for HeaderLayer {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        wrap(inner, self.clone())
+    }
+}
+
+impl Around for HeaderLayer {
+    fn around<'a, T: Send + 'a>(
+        &'a self,
+        _method: &'a str,
+        call: BoxFuture<'a, Result<T, BotError>>,
+    ) -> BoxFuture<'a, Result<T, BotError>> {
+        let mut headers = request_headers();
+        headers.extend(self.headers.iter().cloned());
+        Box::pin(REQUEST_HEADERS.scope(headers, call))
+    }
+}
+
+// Timeouts
+/// Fails requests that take longer than their method's deadline.
+///
+/// Remember that `getUpdates` long-polls: give it more than the polling
+/// timeout.
+#[derive(Debug, Clone, Default)]
+pub struct TimeoutLayer {
+    default: Option<Duration>,
+    per_method: HashMap<String, Duration>,
+}
+
+impl TimeoutLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deadline for methods without their own entry.
+    pub fn default_timeout(mut self, d: Duration) -> Self {
+        self.default = Some(d);
+        self
+    }
+
+    /// Deadline for one method, e.g. `"sendVideo"`. Downloads use `"download"`.
+    pub fn method(mut self, method: impl Into<String>, d: Duration) -> Self {
+        self.per_method.insert(method.into(), d);
+        self
+    }
+}
+
+impl BotClientLayer for TimeoutLayer {
+    fn layer(&self, inner: Arc<dyn BotClient>) -> Arc<dyn BotClient> {
+        wrap(inner, self.clone())
+    }
+}
+
+impl Around for TimeoutLayer {
+    fn around<'a, T: Send + 'a>(
+        &'a self,
+        method: &'a str,
+        call: BoxFuture<'a, Result<T, BotError>>,
+    ) -> BoxFuture<'a, Result<T, BotError>> {
+        let Some(limit) = self.per_method.get(method).copied().or(self.default) else {
+            return call;
+        };
+        Box::pin(async move {
+            tokio::time::timeout(limit, call).await.unwrap_or_else(|_| {
+                Err(BotError::Other(format!(
+                    "{method} timed out after {limit:?}"
+                )))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::Bot;
+
+    /// Records the method and injected headers of every request.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        seen: Mutex<Vec<(String, Vec<(String, String)>)>>,
+    }
+
+    #[async_trait]
+    impl BotClient for Recorder {
+        async fn post_json(
+            &self,
+            url: &str,
+            _body: serde_json::Value,
+        ) -> Result<bytes::Bytes, BotError> {
+            if url.ends_with("/getUpdates") {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            let entry = (method_name(url).to_string(), request_headers());
+            self.seen.lock().unwrap().push(entry);
+            Ok(bytes::Bytes::from(r#"{"ok":true,"result":true}"#))
+        }
+        async fn post_form(&self, _: &str, _: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+            unreachable!()
+        }
+    }
+
+    fn bot(recorder: &Arc<Recorder>) -> Bot {
+        Bot::with_client("1:T", "https://api.telegram.org", Arc::clone(recorder)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn headers_reach_the_transport() {
+        let recorder = Arc::new(Recorder::default());
+        let bot = bot(&recorder).with_layers([
+            HeaderLayer::new().header("X-A", "1"),
+            HeaderLayer::new().header("X-B", "2"),
+        ]);
+        let _: bool = bot.call_api("getMe", serde_json::json!({})).await.unwrap();
+        let seen = recorder.seen.lock().unwrap();
+        assert_eq!(seen[0].0, "getMe");
+        assert_eq!(
+            seen[0].1,
+            [("X-A".into(), "1".into()), ("X-B".into(), "2".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn timing_sees_method_names() {
+        let recorder = Arc::new(Recorder::default());
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&calls);
+        let bot = bot(&recorder)
+            .with_layer(LoggingLayer)
+            .with_layer(TimingLayer::new(move |method, _, ok| {
+                sink.lock().unwrap().push((method.to_string(), ok));
+            }));
+        let _: bool = bot
+            .call_api("getChat", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(*calls.lock().unwrap(), [("getChat".to_string(), true)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_method_timeouts() {
+        let recorder = Arc::new(Recorder::default());
+        let bot = bot(&recorder).with_layer(
+            TimeoutLayer::new()
+                .default_timeout(Duration::from_secs(5))
+                .method("getChat", Duration::from_secs(1)),
+        );
+        let err = bot
+            .call_api::<bool>("getUpdates", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("getUpdates timed out after 5s"));
+
+        let bot = self::bot(&recorder).with_layer(
+            TimeoutLayer::new()
+                .default_timeout(Duration::from_secs(5))
+                .method("getUpdates", Duration::from_secs(90)),
+        );
+        let _: bool = bot
+            .call_api("getUpdates", serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+}
@@ -124,12 +124,19 @@
 mod bot;
 mod chat_id;
 pub mod client;
+pub mod dedup;
+mod download;
 pub mod entities;
 mod error;
 mod helpers; // extension impls on Message, Chat, File, InaccessibleMessage
 mod input_file;
+pub mod layer;
+pub mod offset_store;
 mod polling;
+pub mod rate_limit;
 mod reply_markup;
+pub mod retry;
+pub mod shutdown;
 pub mod types;
 mod updater;
 
@@ -147,17 +154,25 @@ pub mod client_sync;
 
 pub use bot::Bot;
 pub use chat_id::ChatId;
-pub use client::{BotClient, FormBody, FormPart, ReqwestClient};
+pub use client::{BotClient, FormBody, FormPart, ReqwestClient, ResponseBody};
+pub use dedup::{DedupStore, InMemoryDedupStore};
 pub use entities::{parse_entities, parse_entity, MessageEntityExt, ParsedEntity};
-pub use error::BotError;
-pub use input_file::{InputFile, InputFileOrString};
+pub use error::{ApiErrorKind, BotError};
+pub use input_file::{BoxedReader, InputFile, InputFileOrString, ReaderHandle};
+pub use layer::BotClientLayer;
+pub use offset_store::{CommitPolicy, InMemoryOffsetStore, JsonFileOffsetStore, OffsetStore};
 pub use polling::{Poller, UpdateHandler};
+pub use rate_limit::{RateLimit, RateLimitedClient};
 pub use reply_markup::ReplyMarkup;
+pub use retry::RetryPolicy;
+pub use shutdown::StopHandle;
 pub use types::*;
 pub use updater::Updater;
 
+#[cfg(feature = "webhook-tls")]
+pub use webhook::WebhookTls;
 #[cfg(feature = "webhook")]
-pub use webhook::WebhookServer;
+pub use webhook::{ReplyHandler, WebhookReply, WebhookServer};
 
 #[cfg(feature = "bot-mapping")]
 pub use bot_mapping::BotMapping;
@@ -227,9 +242,10 @@ pub mod framework;
 
 // Top-level re-exports for convenience.
 pub use framework::{
-    CallbackQueryHandler, CommandHandler, Context, ContinueGroups, ConversationHandler,
-    ConversationOpts, Dispatcher, DispatcherAction, DispatcherOpts, EndConversation, EndGroups,
-    FilterExt, Handler, HandlerResult, InMemoryStorage, KeyStrategy, MessageHandler, NextState,
+    CallbackQueryHandler, ChatMigrationHandler, CommandHandler, Context, ContinueGroups,
+    ConversationHandler, ConversationOpts, DispatchOutcome, Dispatcher, DispatcherAction,
+    DispatcherOpts, EndConversation, EndGroups, Extensions, FilterExt, Handler, HandlerResult,
+    InMemoryStorage, KeyStrategy, MessageHandler, Middleware, Next, NextState, UpdateKey,
 };
 
 #[cfg(test)]
@@ -0,0 +1,175 @@
+//! Persistent `getUpdates` offsets for [`Poller`](crate::Poller).
+//!
+//! By default a poller starts at offset 0 and relies on Telegram to remember
+//! which updates were confirmed. With an [`OffsetStore`] the poller loads the
+//! last committed offset at startup and commits a new one after every batch,
+//! so a restarted process resumes exactly where the previous one stopped.
+//!
+//! [`CommitPolicy`] decides what "after every batch" means:
+//!
+//! - [`CommitPolicy::AfterReceipt`] commits as soon as a batch has been handed
+//!   to the handler. Updates whose handlers were still running during a crash
+//!   are lost (at-most-once).
+//! - [`CommitPolicy::AfterHandling`] waits for every handler of the batch to
+//!   finish before committing and before fetching the next batch. A crash
+//!   causes the unfinished batch to be delivered again (at-least-once), at the
+//!   cost of processing one batch at a time.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use tgbotrs::{Bot, CommitPolicy, JsonFileOffsetStore, Poller, UpdateHandler};
+//!
+//! # async fn example(bot: Bot, handler: UpdateHandler) -> Result<(), tgbotrs::BotError> {
+//! Poller::new(bot, handler)
+//!     .offset_store(JsonFileOffsetStore::new("/var/lib/mybot/offset.json"))
+//!     .commit_policy(CommitPolicy::AfterHandling)
+//!     .start()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+use crate::BotError;
+
+/// When a poller commits the offset of a batch to its [`OffsetStore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitPolicy {
+    /// As soon as the batch has been handed to the handler (default).
+    #[default]
+    AfterReceipt,
+    /// Once every handler of the batch has finished.
+    AfterHandling,
+}
+
+/// Durable storage for the next `getUpdates` offset.
+#[async_trait]
+pub trait OffsetStore: Send + Sync {
+    /// The last committed offset, or `None` if nothing was stored yet.
+    async fn load(&self) -> Result<Option<i64>, BotError>;
+
+    /// Remember `offset` as the next offset to request.
+    async fn save(&self, offset: i64) -> Result<(), BotError>;
+}
+
+#[async_trait]
+impl<S: OffsetStore + ?Sized> OffsetStore for Box<S> {
+    async fn load(&self) -> Result<Option<i64>, BotError> {
+        (**self).load().await
+    }
+
+    async fn save(&self, offset: i64) -> Result<(), BotError> {
+        (**self).save(offset).await
+    }
+}
+
+#[async_trait]
+impl<S: OffsetStore + ?Sized> OffsetStore for Arc<S> {
+    async fn load(&self) -> Result<Option<i64>, BotError> {
+        (**self).load().await
+    }
+
+    async fn save(&self, offset: i64) -> Result<(), BotError> {
+        (**self).save(offset).await
+    }
+}
+
+/// Keeps the offset in memory; survives poller restarts within one process.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryOffsetStore {
+    offset: Arc<Mutex<Option<i64>>>,
+}
+
+impl InMemoryOffsetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The currently stored offset.
+    pub fn get(&self) -> Option<i64> {
+        *self.offset.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl OffsetStore for InMemoryOffsetStore {
+    async fn load(&self) -> Result<Option<i64>, BotError> {
+        Ok(self.get())
+    }
+
+    async fn save(&self, offset: i64) -> Result<(), BotError> {
+        *self.offset.lock().unwrap() = Some(offset);
+        Ok(())
+    }
+}
+
+/// Stores the offset as `{"offset": N}` in a JSON file.
+///
+/// Writes go to a temporary file that is then renamed over the target, so a
+/// crash mid-write never leaves a corrupt file behind.
+#[derive(Debug, Clone)]
+pub struct JsonFileOffsetStore {
+    path: PathBuf,
+}
+
+impl JsonFileOffsetStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct OffsetFile {
+    offset: i64,
+}
+
+#[async_trait]
+impl OffsetStore for JsonFileOffsetStore {
+    async fn load(&self) -> Result<Option<i64>, BotError> {
+        match tokio::fs::read(&self.path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice::<OffsetFile>(&data)?.offset)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, offset: i64) -> Result<(), BotError> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let data = serde_json::to_vec(&OffsetFile { offset })?;
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        file.write_all(&data).await?;
+        // Flush to disk first, or the rename may land before the data does.
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn json_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("tgbotrs-offset-{}.json", std::process::id()));
+        let store = JsonFileOffsetStore::new(&path);
+        assert_eq!(store.load().await.unwrap(), None);
+        store.save(42).await.unwrap();
+        store.save(43).await.unwrap();
+        assert_eq!(
+            JsonFileOffsetStore::new(&path).load().await.unwrap(),
+            Some(43)
+        );
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -1,9 +1,15 @@
-use crate::gen_methods::GetUpdatesParams;
+use crate::gen_methods::{DeleteWebhookParams, GetUpdatesParams};
+use crate::offset_store::{CommitPolicy, OffsetStore};
+use crate::shutdown::StopHandle;
 use crate::types::Update;
 use crate::{Bot, BotError};
+use futures_util::{stream, Stream};
+use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::task::JoinSet;
 use tracing::{error, info, warn};
 
 /// A function that handles an incoming update.
@@ -20,6 +26,16 @@ pub struct Poller {
     limit: i64,
     /// Update types to receive (empty = all).
     allowed_updates: Vec<String>,
+    /// Signals the polling loop to shut down.
+    stop: StopHandle,
+    /// How long to wait for in-flight handlers after stopping.
+    shutdown_timeout: Duration,
+    /// Where the next offset is loaded from and committed to.
+    offset_store: Option<Arc<dyn OffsetStore>>,
+    /// When offsets are committed to `offset_store`.
+    commit_policy: CommitPolicy,
+    /// Call `deleteWebhook` before polling; the value is `drop_pending_updates`.
+    delete_webhook: Option<bool>,
 }
 
 impl Poller {
@@ -31,6 +47,11 @@ impl Poller {
             timeout: 30,
             limit: 100,
             allowed_updates: vec![],
+            stop: StopHandle::new(),
+            shutdown_timeout: Duration::from_secs(10),
+            offset_store: None,
+            commit_policy: CommitPolicy::default(),
+            delete_webhook: None,
         }
     }
 
@@ -52,9 +73,61 @@ impl Poller {
         self
     }
 
-    /// Start polling. Runs until the process exits or an unrecoverable error occurs.
+    /// Use an existing [`StopHandle`], e.g. one shared with other services.
+    pub fn with_stop_handle(mut self, stop: StopHandle) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Handle that stops this poller; see [`crate::shutdown`].
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// How long to wait for in-flight handlers after a stop (default 10 s).
+    /// Handlers still running after that are aborted.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Resume from and commit offsets to `store`; see [`crate::offset_store`].
+    pub fn offset_store(mut self, store: impl OffsetStore + 'static) -> Self {
+        self.offset_store = Some(Arc::new(store));
+        self
+    }
+
+    /// When offsets are committed to the offset store (default
+    /// [`CommitPolicy::AfterReceipt`]).
+    pub fn commit_policy(mut self, policy: CommitPolicy) -> Self {
+        self.commit_policy = policy;
+        self
+    }
+
+    /// Remove any registered webhook before polling, optionally dropping the
+    /// updates Telegram is still holding for it.
+    pub fn delete_webhook(mut self, drop_pending_updates: bool) -> Self {
+        self.delete_webhook = Some(drop_pending_updates);
+        self
+    }
+
+    /// Start polling. Runs until stopped through [`Poller::stop_handle`].
+    ///
+    /// Fails if the webhook cannot be deleted or the offset store read at
+    /// startup, or if Telegram answers `409 Conflict` because a webhook is
+    /// still set or another instance is polling ([`BotError::is_conflict`]).
+    /// Every other error is retried.
     pub async fn start(self) -> Result<(), BotError> {
-        let mut offset: i64 = 0;
+        if let Some(drop_pending) = self.delete_webhook {
+            let params = DeleteWebhookParams::new().drop_pending_updates(drop_pending);
+            self.bot.delete_webhook(Some(params)).await?;
+            info!(drop_pending, "webhook deleted");
+        }
+        let mut offset: i64 = match &self.offset_store {
+            Some(store) => store.load().await?.unwrap_or(0),
+            None => 0,
+        };
+        let after_handling = self.commit_policy == CommitPolicy::AfterHandling;
         // Clone once — this Vec is immutable for the lifetime of the poller.
         let allowed_updates = if self.allowed_updates.is_empty() {
             None
@@ -63,8 +136,10 @@ impl Poller {
         };
 
         info!("polling started");
+        let mut tasks = JoinSet::new();
+        let mut fatal = None;
 
-        loop {
+        while !self.stop.is_stopped() {
             let mut params = GetUpdatesParams::new()
                 .offset(offset)
                 .timeout(self.timeout)
@@ -74,29 +149,33 @@ impl Poller {
                 params = params.allowed_updates(au.clone());
             }
 
-            let updates = match self.bot.get_updates(Some(params)).await {
+            // A stop cancels the pending long poll; its updates were not
+            // confirmed, so Telegram will deliver them again next time.
+            let result = tokio::select! {
+                _ = self.stop.stopped() => break,
+                r = self.bot.get_updates(Some(params)) => r,
+            };
+            let updates = match result {
                 Ok(u) => u,
+                Err(e) if e.is_conflict() => {
+                    error!(error = %e, "getUpdates conflict: a webhook is set or another instance is polling");
+                    fatal = Some(e);
+                    break;
+                }
                 Err(e) => {
-                    // On flood-wait (429) honour the server-supplied retry_after.
-                    // On any other error back off 3 s to avoid hammering the API.
-                    let sleep_secs = match &e {
-                        BotError::Api {
-                            retry_after: Some(secs),
-                            ..
-                        } => {
-                            warn!(retry_after = secs, "flood-wait on getUpdates");
-                            *secs as u64
-                        }
-                        _ => {
-                            error!(error = %e, "getUpdates error, retrying in 3 s");
-                            3
-                        }
-                    };
-                    tokio::time::sleep(Duration::from_secs(sleep_secs)).await;
-                    continue;
+                    if e.flood_wait_seconds().is_none() {
+                        error!(error = %e, "getUpdates error, retrying in 3 s");
+                    }
+                    tokio::select! {
+                        _ = self.stop.stopped() => break,
+                        _ = tokio::time::sleep(error_delay(&e)) => continue,
+                    }
                 }
             };
 
+            if updates.is_empty() {
+                continue;
+            }
             for update in updates {
                 offset = update.update_id + 1;
                 let bot_clone = self.bot.clone();
@@ -104,8 +183,146 @@ impl Poller {
 
                 // Single spawn per update. Tokio catches panics at the task
                 // boundary — a panic aborts only this task, not the poller.
-                tokio::spawn(fut);
+                tasks.spawn(fut);
             }
+
+            if after_handling {
+                // Hold the next getUpdates back until the batch is handled, so
+                // neither Telegram nor the store moves past unhandled updates.
+                let drain = async { while tasks.join_next().await.is_some() {} };
+                tokio::select! {
+                    _ = self.stop.stopped() => break,
+                    _ = drain => {}
+                }
+            } else {
+                // Reap finished handlers so the set only holds in-flight ones.
+                while tasks.try_join_next().is_some() {}
+            }
+            self.commit(offset).await;
         }
+
+        info!("polling stopping");
+        let drain = async { while tasks.join_next().await.is_some() {} };
+        let drained = tokio::time::timeout(self.shutdown_timeout, drain)
+            .await
+            .is_ok();
+        if !drained {
+            warn!(
+                remaining = tasks.len(),
+                "shutdown timeout reached, aborting in-flight handlers"
+            );
+            tasks.abort_all();
+        }
+        // With AfterHandling, aborted handlers leave their batch unconfirmed
+        // so it is delivered again on the next start.
+        if offset != 0 && (drained || !after_handling) {
+            // After a conflict getUpdates would only fail again.
+            if fatal.is_none() {
+                self.confirm_offset(offset).await;
+            }
+            self.commit(offset).await;
+        }
+        info!("polling stopped");
+        fatal.map_or(Ok(()), Err)
+    }
+
+    /// Save `offset` to the offset store, if any. Failures are only logged.
+    async fn commit(&self, offset: i64) {
+        if let Some(store) = &self.offset_store {
+            if let Err(e) = store.save(offset).await {
+                warn!(error = %e, offset, "failed to commit update offset");
+            }
+        }
+    }
+
+    /// Tell Telegram that every update before `offset` has been handled.
+    async fn confirm_offset(&self, offset: i64) {
+        let params = GetUpdatesParams::new().offset(offset).timeout(0).limit(1);
+        if let Err(e) = self.bot.get_updates(Some(params)).await {
+            warn!(error = %e, offset, "failed to confirm last update offset");
+        }
+    }
+}
+
+/// How long to wait before the next `getUpdates` after `e`.
+///
+/// On flood-wait (429) honour the server-supplied retry_after. On any other
+/// error back off 3 s to avoid hammering the API.
+fn error_delay(e: &BotError) -> Duration {
+    match e.flood_wait_seconds() {
+        Some(secs) => {
+            warn!(retry_after = secs, "flood-wait on getUpdates");
+            Duration::from_secs(secs.max(0) as u64)
+        }
+        None => Duration::from_secs(3),
     }
 }
+
+impl Bot {
+    /// Long-poll updates as a [`Stream`].
+    ///
+    /// `getUpdates` is only called once the consumer has pulled every update
+    /// from the previous batch, and each call confirms that batch. Flood
+    /// waits are slept through; other errors are yielded, and the next
+    /// request waits 3 s. Without `params` this long-polls with a 30 s
+    /// timeout.
+    ///
+    /// ```rust,no_run
+    /// use futures_util::StreamExt;
+    /// # async fn example(bot: tgbotrs::Bot) {
+    /// let mut updates = std::pin::pin!(bot.update_stream(None));
+    /// while let Some(update) = updates.next().await {
+    ///     match update {
+    ///         Ok(update) => println!("update {}", update.update_id),
+    ///         Err(e) => eprintln!("polling error: {e}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn update_stream(
+        &self,
+        params: Option<GetUpdatesParams>,
+    ) -> impl Stream<Item = Result<Update, BotError>> + Send + 'static {
+        let params = params.unwrap_or_else(|| GetUpdatesParams::new().timeout(30));
+        let state = StreamState {
+            bot: self.clone(),
+            offset: params.offset.unwrap_or(0),
+            params,
+            buffered: VecDeque::new(),
+            delay: None,
+        };
+        stream::unfold(state, |mut st| async move {
+            loop {
+                if let Some(update) = st.buffered.pop_front() {
+                    return Some((Ok(update), st));
+                }
+                if let Some(delay) = st.delay.take() {
+                    tokio::time::sleep(delay).await;
+                }
+                let params = st.params.clone().offset(st.offset);
+                match st.bot.get_updates(Some(params)).await {
+                    Ok(updates) => {
+                        if let Some(last) = updates.last() {
+                            st.offset = last.update_id + 1;
+                        }
+                        st.buffered.extend(updates);
+                    }
+                    Err(e) => {
+                        st.delay = Some(error_delay(&e));
+                        if e.flood_wait_seconds().is_none() {
+                            return Some((Err(e), st));
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+struct StreamState {
+    bot: Bot,
+    params: GetUpdatesParams,
+    offset: i64,
+    buffered: VecDeque<Update>,
+    delay: Option<Duration>,
+}
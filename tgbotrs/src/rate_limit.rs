@@ -0,0 +1,342 @@
+//! Outbound throttling that keeps a bot inside Telegram's sending limits.
+//!
+//! Telegram allows roughly 30 messages per second overall, one message per
+//! second in a private chat and 20 messages per minute in a group. Going over
+//! those limits earns `429 Too Many Requests`. [`RateLimitedClient`] wraps any
+//! [`BotClient`] and delays message-sending requests (`sendX`, `forwardX`,
+//! `copyX`) until they fit; every other request passes straight through.
+//!
+//! The chat is read from the `chat_id` field of the request. Each request
+//! takes the earliest global slot that is free at or after its chat's next
+//! allowed send, so one busy chat never holds up messages to other chats.
+//!
+//! Delays use [`tokio::time`], so tests can run against a mock client on a
+//! paused, virtual clock (`#[tokio::test(start_paused = true)]`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use tgbotrs::{Bot, RateLimit};
+//!
+//! # async fn example() -> Result<(), tgbotrs::BotError> {
+//! let bot = Bot::new("YOUR_TOKEN")
+//!     .await?
+//!     .with_rate_limit(RateLimit::new().global_per_second(25));
+//!
+//! for chat_id in [111i64, 222, 333] {
+//!     bot.send_message(chat_id, "Broadcast", None).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    ops::Bound::{Excluded, Unbounded},
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::{
+    client::{method_name, BotClient, FormBody, FormPart, ResponseBody},
+    BotError,
+};
+
+/// Sending limits applied by [`RateLimitedClient`]. A limit of `0` disables it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Messages per second across all chats (default 30).
+    pub global_per_second: u32,
+    /// Messages per second to a single private chat (default 1).
+    pub private_per_second: u32,
+    /// Messages per minute to a single group, supergroup or channel (default 20).
+    pub group_per_minute: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            global_per_second: 30,
+            private_per_second: 1,
+            group_per_minute: 20,
+        }
+    }
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global_per_second(mut self, n: u32) -> Self {
+        self.global_per_second = n;
+        self
+    }
+
+    pub fn private_per_second(mut self, n: u32) -> Self {
+        self.private_per_second = n;
+        self
+    }
+
+    pub fn group_per_minute(mut self, n: u32) -> Self {
+        self.group_per_minute = n;
+        self
+    }
+}
+
+/// A [`BotClient`] that throttles message-sending requests before handing
+/// them to the wrapped client.
+///
+/// Usually installed with [`Bot::with_rate_limit`](crate::Bot::with_rate_limit).
+pub struct RateLimitedClient<C> {
+    inner: C,
+    global_gap: Option<Duration>,
+    private_gap: Option<Duration>,
+    group_gap: Option<Duration>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Start times of scheduled sends, for the global limit.
+    global: BTreeSet<Instant>,
+    /// Earliest time the next message may go to each chat.
+    chats: HashMap<String, Instant>,
+}
+
+impl<C> RateLimitedClient<C> {
+    pub fn new(inner: C, limit: RateLimit) -> Self {
+        Self {
+            inner,
+            global_gap: gap(Duration::from_secs(1), limit.global_per_second),
+            private_gap: gap(Duration::from_secs(1), limit.private_per_second),
+            group_gap: gap(Duration::from_secs(60), limit.group_per_minute),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Wait until a request to `method` for `chat_id` fits within the limits.
+    async fn throttle(&self, method: &str, chat_id: Option<String>) {
+        if !is_send_method(method) {
+            return;
+        }
+        let at = self.reserve(chat_id);
+        tokio::time::sleep_until(at).await;
+    }
+
+    /// Book the earliest slot allowed by both the chat and the global limit.
+    fn reserve(&self, chat_id: Option<String>) -> Instant {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let chat_gap = chat_id.as_deref().and_then(|id| {
+            if id.starts_with('-') || id.starts_with('@') {
+                self.group_gap
+            } else {
+                self.private_gap
+            }
+        });
+
+        let mut at = now;
+        if let (Some(id), Some(_)) = (&chat_id, chat_gap) {
+            if let Some(next) = state.chats.get(id) {
+                at = at.max(*next);
+            }
+        }
+
+        if let Some(gap) = self.global_gap {
+            // Drop bookings that can no longer conflict with anything.
+            if let Some(cutoff) = now.checked_sub(gap) {
+                state.global = state.global.split_off(&cutoff);
+            }
+            // Slide forward until no booking lies within `gap` of `at`.
+            loop {
+                let lo = at.checked_sub(gap).map_or(Unbounded, Excluded);
+                let Some(last) = state.global.range((lo, Excluded(at + gap))).next_back() else {
+                    break;
+                };
+                at = *last + gap;
+            }
+            state.global.insert(at);
+        }
+
+        if let (Some(id), Some(gap)) = (chat_id, chat_gap) {
+            state.chats.insert(id, at + gap);
+            if state.chats.len() > 1024 {
+                state.chats.retain(|_, next| *next > now);
+            }
+        }
+        at
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for RateLimitedClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitedClient")
+            .field("inner", &self.inner)
+            .field("global_gap", &self.global_gap)
+            .field("private_gap", &self.private_gap)
+            .field("group_gap", &self.group_gap)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<C: BotClient> BotClient for RateLimitedClient<C> {
+    async fn post_json(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<bytes::Bytes, BotError> {
+        let chat_id = body.get("chat_id").and_then(|v| match v {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        });
+        self.throttle(method_name(url), chat_id).await;
+        self.inner.post_json(url, body).await
+    }
+
+    async fn post_form(&self, url: &str, parts: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+        let chat_id = parts.iter().find_map(|p| match &p.body {
+            FormBody::Text(id) if p.name == "chat_id" => Some(id.clone()),
+            _ => None,
+        });
+        self.throttle(method_name(url), chat_id).await;
+        self.inner.post_form(url, parts).await
+    }
+
+    async fn get(&self, url: &str) -> Result<bytes::Bytes, BotError> {
+        self.inner.get(url).await
+    }
+
+    async fn get_stream(&self, url: &str) -> Result<ResponseBody, BotError> {
+        self.inner.get_stream(url).await
+    }
+}
+
+fn gap(window: Duration, n: u32) -> Option<Duration> {
+    (n > 0).then(|| window / n)
+}
+
+/// Requests that deliver a message and therefore count towards the limits.
+fn is_send_method(method: &str) -> bool {
+    method != "sendChatAction"
+        && ["send", "forward", "copy"]
+            .iter()
+            .any(|p| method.starts_with(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Records the virtual time at which each request reached the transport.
+    #[derive(Debug, Default)]
+    struct ClockClient {
+        sent: Mutex<Vec<(String, Instant)>>,
+    }
+
+    #[async_trait]
+    impl BotClient for ClockClient {
+        async fn post_json(
+            &self,
+            url: &str,
+            body: serde_json::Value,
+        ) -> Result<bytes::Bytes, BotError> {
+            let key = format!("{}:{}", method_name(url), body["chat_id"]);
+            self.sent.lock().unwrap().push((key, Instant::now()));
+            Ok(bytes::Bytes::from(r#"{"ok":true,"result":true}"#))
+        }
+        async fn post_form(&self, _: &str, _: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+            unreachable!()
+        }
+    }
+
+    async fn send_all(
+        limit: RateLimit,
+        requests: &[(&'static str, serde_json::Value)],
+    ) -> Vec<(String, Duration)> {
+        let client = Arc::new(RateLimitedClient::new(ClockClient::default(), limit));
+        let start = Instant::now();
+        let tasks: Vec<_> = requests
+            .iter()
+            .cloned()
+            .map(|(method, chat_id)| {
+                let client = Arc::clone(&client);
+                tokio::spawn(async move {
+                    let url = format!("https://api.telegram.org/bot1:T/{method}");
+                    client
+                        .post_json(&url, serde_json::json!({ "chat_id": chat_id }))
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let sent = client.inner.sent.lock().unwrap();
+        sent.iter()
+            .map(|(key, at)| (key.clone(), *at - start))
+            .collect()
+    }
+
+    fn times(sent: &[(String, Duration)], key: &str) -> Vec<Duration> {
+        sent.iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, t)| *t)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn private_chat_gets_one_message_per_second() {
+        let req = ("sendMessage", serde_json::json!(42));
+        let sent = send_all(RateLimit::new(), &[req.clone(), req.clone(), req]).await;
+        let t = times(&sent, "sendMessage:42");
+        assert_eq!(t, [0, 1, 2].map(Duration::from_secs));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn groups_are_limited_per_minute() {
+        let req = ("sendMessage", serde_json::json!("-100123"));
+        let sent = send_all(RateLimit::new(), &vec![req; 3]).await;
+        let t = times(&sent, "sendMessage:\"-100123\"");
+        assert_eq!(t, [0, 3, 6].map(Duration::from_secs));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn busy_chat_does_not_block_others() {
+        let hot = ("sendMessage", serde_json::json!(1));
+        let other = ("sendMessage", serde_json::json!(2));
+        let sent = send_all(RateLimit::new(), &[hot.clone(), hot.clone(), hot, other]).await;
+        assert!(times(&sent, "sendMessage:2")[0] < Duration::from_millis(100));
+        assert_eq!(times(&sent, "sendMessage:1")[2], Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn global_limit_spreads_broadcasts() {
+        let requests: Vec<_> = (0..60)
+            .map(|id| ("sendMessage", serde_json::json!(id)))
+            .collect();
+        let sent = send_all(RateLimit::new().global_per_second(30), &requests).await;
+        let mut t: Vec<_> = sent.iter().map(|(_, t)| *t).collect();
+        t.sort();
+        // No more than 30 messages within any one-second window.
+        for w in t.windows(31) {
+            assert!(w[30] - w[0] >= Duration::from_millis(999));
+        }
+        assert!(*t.last().unwrap() < Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_methods_pass_through() {
+        let req = ("getChat", serde_json::json!(42));
+        let sent = send_all(RateLimit::new(), &vec![req; 5]).await;
+        assert!(times(&sent, "getChat:42").iter().all(|t| t.is_zero()));
+    }
+}
@@ -0,0 +1,301 @@
+//! Opt-in retries for Bot API calls.
+//!
+//! Attach a [`RetryPolicy`] with [`Bot::with_retry`](crate::Bot::with_retry)
+//! and every call made through `call_api`, `call_api_multipart`,
+//! `call_api_with_file` and `call_api_with_attachments` (so every generated
+//! method) is retried according to it.
+//!
+//! Flood-wait errors (429) and failed connections are always safe to retry:
+//! Telegram never processed the request. Timeouts, dropped connections and
+//! 5xx errors may have been processed, so they are only retried for methods
+//! that don't create anything (`getX`, `setX`, `editX`, `deleteX`, ...) unless
+//! [`RetryPolicy::retry_non_idempotent`] is enabled.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tgbotrs::{Bot, RetryPolicy};
+//!
+//! # async fn example() -> Result<(), tgbotrs::BotError> {
+//! let bot = Bot::new("YOUR_TOKEN")
+//!     .await?
+//!     .with_retry(RetryPolicy::new().max_attempts(5).base_delay(Duration::from_secs(1)));
+//!
+//! // Flood waits are now slept through instead of returned.
+//! bot.send_message(123456789i64, "Hello!", None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{future::Future, time::Duration};
+
+use tracing::warn;
+
+use crate::BotError;
+
+/// Which failures to retry and how long to wait between attempts.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one (default 3).
+    pub max_attempts: u32,
+    /// Backoff before the first retry; doubled on each further retry (default 500 ms).
+    pub base_delay: Duration,
+    /// Upper bound on the exponential backoff (default 30 s).
+    pub max_delay: Duration,
+    /// Randomise each backoff between half and the full delay (default `true`).
+    pub jitter: bool,
+    /// Retry 429 errors after the server-supplied `retry_after` (default `true`).
+    pub retry_flood_wait: bool,
+    /// Give up on flood waits longer than this (default 5 min).
+    pub max_retry_after: Duration,
+    /// Retry transport failures: refused connections, timeouts, resets (default `true`).
+    pub retry_network: bool,
+    /// Retry 5xx errors returned by the Bot API (default `true`).
+    pub retry_server_errors: bool,
+    /// Also retry timeouts and 5xx errors for methods that may have taken
+    /// effect, like `sendMessage` (default `false`). Can cause duplicates.
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retry_flood_wait: true,
+            max_retry_after: Duration::from_secs(300),
+            retry_network: true,
+            retry_server_errors: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_attempts(mut self, n: u32) -> Self {
+        self.max_attempts = n.max(1);
+        self
+    }
+
+    pub fn base_delay(mut self, d: Duration) -> Self {
+        self.base_delay = d;
+        self
+    }
+
+    pub fn max_delay(mut self, d: Duration) -> Self {
+        self.max_delay = d;
+        self
+    }
+
+    pub fn jitter(mut self, on: bool) -> Self {
+        self.jitter = on;
+        self
+    }
+
+    pub fn retry_flood_wait(mut self, on: bool) -> Self {
+        self.retry_flood_wait = on;
+        self
+    }
+
+    pub fn max_retry_after(mut self, d: Duration) -> Self {
+        self.max_retry_after = d;
+        self
+    }
+
+    pub fn retry_network(mut self, on: bool) -> Self {
+        self.retry_network = on;
+        self
+    }
+
+    pub fn retry_server_errors(mut self, on: bool) -> Self {
+        self.retry_server_errors = on;
+        self
+    }
+
+    pub fn retry_non_idempotent(mut self, on: bool) -> Self {
+        self.retry_non_idempotent = on;
+        self
+    }
+
+    /// How long to wait before retrying `method` after `err` on the given
+    /// 1-based `attempt`, or `None` to give up.
+    pub fn retry_delay(&self, method: &str, err: &BotError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let safe = self.retry_non_idempotent || is_idempotent(method);
+        match err {
+            BotError::Api {
+                code: 429,
+                retry_after,
+                ..
+            } => {
+                if !self.retry_flood_wait {
+                    return None;
+                }
+                match retry_after {
+                    Some(secs) => {
+                        let wait = Duration::from_secs((*secs).max(0) as u64);
+                        (wait <= self.max_retry_after).then_some(wait)
+                    }
+                    None => Some(self.backoff(attempt)),
+                }
+            }
+            BotError::Api { code, .. } if *code >= 500 => {
+                (self.retry_server_errors && safe).then(|| self.backoff(attempt))
+            }
+            // The connection was never established, so nothing was sent.
+            BotError::Http(e) if e.is_connect() => {
+                self.retry_network.then(|| self.backoff(attempt))
+            }
+            BotError::Http(e) if e.is_timeout() || e.is_request() || e.is_body() => {
+                (self.retry_network && safe).then(|| self.backoff(attempt))
+            }
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(exp).min(self.max_delay);
+        if self.jitter {
+            delay / 2 + delay.mul_f64(random_unit() / 2.0)
+        } else {
+            delay
+        }
+    }
+
+    /// Run `op` until it succeeds or the policy gives up.
+    pub(crate) async fn run<T, F, Fut>(&self, method: &str, mut op: F) -> Result<T, BotError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, BotError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(v) => return Ok(v),
+                Err(e) => {
+                    let Some(delay) = self.retry_delay(method, &e, attempt) else {
+                        return Err(e);
+                    };
+                    warn!(method, attempt, ?delay, error = %e, "retrying API call");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Methods that create something (messages, invoices, sticker sets, ...) are
+/// unsafe to repeat when the first attempt may have gone through.
+fn is_idempotent(method: &str) -> bool {
+    const CREATING: &[&str] = &[
+        "send", "forward", "copy", "post", "create", "upload", "add", "gift",
+    ];
+    !CREATING.iter().any(|p| method.starts_with(p))
+}
+
+/// Cheap uniform value in `[0, 1)` for jitter; no need for a real RNG here.
+fn random_unit() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+    h.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    (h.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api(code: i64, retry_after: Option<i64>) -> BotError {
+        BotError::Api {
+            code,
+            description: String::new(),
+            retry_after,
+            migrate_to_chat_id: None,
+        }
+    }
+
+    #[test]
+    fn flood_wait_uses_retry_after_even_for_send() {
+        let p = RetryPolicy::new();
+        assert_eq!(
+            p.retry_delay("sendMessage", &api(429, Some(7)), 1),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(p.retry_delay("sendMessage", &api(429, Some(7)), 3), None);
+        assert_eq!(
+            p.max_retry_after(Duration::from_secs(5)).retry_delay(
+                "sendMessage",
+                &api(429, Some(7)),
+                1
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn server_errors_respect_idempotency() {
+        let p = RetryPolicy::new().jitter(false);
+        assert_eq!(
+            p.retry_delay("getChat", &api(502, None), 2),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(p.retry_delay("sendMessage", &api(502, None), 1), None);
+        assert!(p
+            .retry_non_idempotent(true)
+            .retry_delay("sendMessage", &api(502, None), 1)
+            .is_some());
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let p = RetryPolicy::new();
+        assert_eq!(p.retry_delay("getChat", &api(400, None), 1), None);
+        assert_eq!(p.retry_delay("getChat", &BotError::InvalidToken, 1), None);
+    }
+
+    #[test]
+    fn backoff_is_capped_and_jittered() {
+        let p = RetryPolicy::new().max_delay(Duration::from_secs(2));
+        for attempt in 1..10 {
+            let d = p.backoff(attempt);
+            assert!(d <= Duration::from_secs(2));
+            assert!(d >= p.base_delay.min(p.max_delay) / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn run_retries_until_success() {
+        let p = RetryPolicy::new().base_delay(Duration::from_millis(1));
+        let mut calls = 0;
+        let out = p
+            .run("getMe", || {
+                calls += 1;
+                let n = calls;
+                async move {
+                    if n < 3 {
+                        Err(api(429, Some(0)))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(out.unwrap(), 3);
+    }
+}
@@ -0,0 +1,136 @@
+//! Graceful shutdown for [`Poller`](crate::Poller) and [`Updater`](crate::Updater).
+//!
+//! Every poller owns a [`StopHandle`]. Calling [`StopHandle::stop`] makes the
+//! poller stop calling `getUpdates`, confirm the last processed offset to
+//! Telegram (so nothing is redelivered on the next start) and wait, up to the
+//! shutdown timeout, for in-flight handlers before returning.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use tgbotrs::{Bot, Dispatcher, DispatcherOpts, Updater};
+//!
+//! # async fn example() -> Result<(), tgbotrs::BotError> {
+//! let bot = Bot::new("YOUR_TOKEN").await?;
+//! let updater = Updater::new(bot, Dispatcher::new(DispatcherOpts::default()));
+//!
+//! // Stop on Ctrl-C or SIGTERM (e.g. a Kubernetes rolling deploy).
+//! updater.stop_handle().stop_on_signal();
+//!
+//! updater.start_polling().await?; // returns once shut down
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use tokio::sync::Notify;
+use tracing::info;
+
+/// Cloneable handle that asks a running poller to shut down.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    inner: Arc<StopState>,
+}
+
+#[derive(Debug, Default)]
+struct StopState {
+    stopped: AtomicBool,
+    notify: Notify,
+}
+
+impl StopHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request shutdown. Idempotent.
+    pub fn stop(&self) {
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Whether [`StopHandle::stop`] has been called.
+    pub fn is_stopped(&self) -> bool {
+        self.inner.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once shutdown has been requested.
+    pub async fn stopped(&self) {
+        let mut notified = pin!(self.inner.notify.notified());
+        notified.as_mut().enable();
+        if self.is_stopped() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Spawn a task that calls [`StopHandle::stop`] on Ctrl-C, or on
+    /// SIGTERM on Unix.
+    pub fn stop_on_signal(&self) {
+        let handle = self.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            info!("shutdown signal received");
+            handle.stop();
+        });
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Counts running tasks so shutdown can wait for them to finish.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl InFlight {
+    /// Mark a task as started; it counts until the guard is dropped.
+    pub(crate) fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(Arc::clone(self))
+    }
+
+    /// Resolve once no tasks are running.
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            let mut notified = pin!(self.idle.notified());
+            notified.as_mut().enable();
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+pub(crate) struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
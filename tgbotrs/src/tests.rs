@@ -60,6 +60,36 @@ mod serde_roundtrip {
         assert_eq!(e.flood_wait_seconds(), Some(5));
         assert!(e.is_api_error_code(429));
         assert!(!e.is_api_error_code(400));
+        assert_eq!(e.kind(), Some(crate::ApiErrorKind::TooManyRequests));
+    }
+
+    #[test]
+    fn bot_error_kind_from_description() {
+        use crate::{ApiErrorKind, BotError};
+        let api = |code, description: &str| BotError::Api {
+            code,
+            description: description.into(),
+            retry_after: None,
+            migrate_to_chat_id: None,
+        };
+        assert!(api(403, "Forbidden: bot was blocked by the user").is_blocked_by_user());
+        assert!(api(
+            400,
+            "Bad Request: message is not modified: specified new message content and reply markup are exactly the same"
+        )
+        .is_message_not_modified());
+        assert!(api(400, "Bad Request: message to edit not found").is_message_to_edit_not_found());
+        assert!(api(400, "Bad Request: chat not found").is_chat_not_found());
+        assert_eq!(
+            api(403, "Forbidden: bot was kicked from the supergroup chat").kind(),
+            Some(ApiErrorKind::BotKicked)
+        );
+        assert_eq!(
+            api(400, "Bad Request: something new").kind(),
+            Some(ApiErrorKind::Unknown)
+        );
+        assert_eq!(BotError::InvalidToken.kind(), None);
+        assert!(!BotError::InvalidToken.is_blocked_by_user());
     }
 
     #[test]
@@ -97,7 +127,7 @@ mod dispatcher_tests {
     use crate::{
         framework::{
             context::Context,
-            dispatcher::{Dispatcher, DispatcherOpts},
+            dispatcher::{Dispatcher, DispatcherOpts, UpdateKey},
             handler::{Handler, HandlerResult},
         },
         types::Update,
@@ -200,6 +230,130 @@ mod dispatcher_tests {
         // "a" was removed so only "b" fires
         assert_eq!(got, vec!["b"]);
     }
+
+    fn chat_update(id: i64, chat: i64) -> Update {
+        serde_json::from_value(serde_json::json!({
+            "update_id": id,
+            "message": {
+                "message_id": id,
+                "date": 0,
+                "chat": { "id": chat, "type": "private" },
+                "text": "hi"
+            }
+        }))
+        .unwrap()
+    }
+
+    /// Earlier updates take longer, so unordered dispatch finishes them last.
+    struct SleepyHandler(Arc<Mutex<Vec<(i64, i64)>>>);
+
+    #[async_trait]
+    impl Handler for SleepyHandler {
+        fn name(&self) -> &str {
+            "sleepy"
+        }
+        fn check_update(&self, _: &Context) -> bool {
+            true
+        }
+        async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+            let id = ctx.update.update_id;
+            tokio::time::sleep(std::time::Duration::from_secs(10 - id as u64)).await;
+            let chat = ctx.effective_chat().unwrap().id;
+            self.0.lock().unwrap().push((chat, id));
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ordered_dispatch_serializes_per_chat() {
+        let done = Arc::new(Mutex::new(vec![]));
+        let mut dp = Dispatcher::new(DispatcherOpts::default().ordered_by(UpdateKey::Chat));
+        dp.add_handler(SleepyHandler(Arc::clone(&done)));
+        let bot = Bot::new_unverified("123456789:fake_token_for_testing").unwrap();
+
+        for (id, chat) in [(1, 100), (2, 100), (3, 200), (4, 100)] {
+            dp.dispatch(bot.clone(), chat_update(id, chat));
+        }
+        let start = tokio::time::Instant::now();
+        dp.wait_idle().await;
+
+        let done = done.lock().unwrap().clone();
+        let chat_100: Vec<_> = done
+            .iter()
+            .filter(|(c, _)| *c == 100)
+            .map(|(_, id)| *id)
+            .collect();
+        assert_eq!(chat_100, [1, 2, 4]);
+        // Chat 200 ran alongside chat 100 rather than after it.
+        assert_eq!(done[0], (200, 3));
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(9 + 8 + 6));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ordered_queue_drops_overflow() {
+        let done = Arc::new(Mutex::new(vec![]));
+        let opts = DispatcherOpts::default()
+            .ordered_by(UpdateKey::custom(|_| Some("all".into())))
+            .ordered_queue_size(1);
+        let mut dp = Dispatcher::new(opts);
+        dp.add_handler(SleepyHandler(Arc::clone(&done)));
+        let bot = Bot::new_unverified("123456789:fake_token_for_testing").unwrap();
+
+        for id in 1..=3 {
+            dp.dispatch(bot.clone(), chat_update(id, 100));
+        }
+        dp.wait_idle().await;
+
+        assert_eq!(*done.lock().unwrap(), [(100, 1), (100, 2)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ordered_queue_keeps_every_update_by_default() {
+        let done = Arc::new(Mutex::new(vec![]));
+        let opts = DispatcherOpts::default().ordered_by(UpdateKey::custom(|_| Some("all".into())));
+        let mut dp = Dispatcher::new(opts);
+        dp.add_handler(SleepyHandler(Arc::clone(&done)));
+        let bot = Bot::new_unverified("123456789:fake_token_for_testing").unwrap();
+
+        for id in 0..200 {
+            dp.dispatch(bot.clone(), chat_update(id % 10, 100));
+        }
+        dp.wait_idle().await;
+
+        let done = done.lock().unwrap();
+        assert_eq!(done.len(), 200);
+        assert!(done.iter().zip(0..).all(|(&(_, id), n)| id == n % 10));
+    }
+
+    #[test]
+    fn opts_fields_support_struct_update_syntax() {
+        let opts = DispatcherOpts {
+            max_routines: Some(4),
+            ordered_by: Some(UpdateKey::Chat),
+            ..Default::default()
+        };
+        assert_eq!(opts.max_routines, Some(4));
+        assert!(opts.middlewares.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropping_the_dispatcher_keeps_handlers_running() {
+        let done = Arc::new(Mutex::new(vec![]));
+        for opts in [
+            DispatcherOpts::default(),
+            DispatcherOpts::default().ordered_by(UpdateKey::Chat),
+        ] {
+            let mut dp = Dispatcher::new(opts);
+            dp.add_handler(SleepyHandler(Arc::clone(&done)));
+            let bot = Bot::new_unverified("123456789:fake_token_for_testing").unwrap();
+            dp.dispatch(bot, chat_update(9, 100));
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            drop(dp);
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        assert_eq!(*done.lock().unwrap(), [(100, 9), (100, 9)]);
+    }
 }
 
 #[cfg(test)]
@@ -275,3 +429,2057 @@ mod conversation_tests {
         assert!(e.to_string().contains("EndConversation"));
     }
 }
+
+#[cfg(test)]
+mod mock {
+    use crate::{
+        client::{method_name, BotClient, FormBody, FormPart},
+        Bot, BotError,
+    };
+    use async_trait::async_trait;
+    use std::{
+        fmt,
+        sync::{Arc, Mutex, MutexGuard},
+    };
+
+    /// A request received by a [`MockClient`].
+    #[derive(Debug)]
+    pub(crate) struct Call {
+        pub url: String,
+        /// Bot API method; empty for file downloads.
+        pub method: String,
+        /// The JSON body, or the text parts of a form (parsed as JSON where
+        /// they are JSON).
+        pub body: serde_json::Value,
+        /// All parts of a form request.
+        pub form: Option<Vec<FormPart>>,
+    }
+
+    /// How a [`MockClient`] answers a request.
+    pub(crate) enum Reply {
+        Body(bytes::Bytes),
+        /// Never answer, like a long poll without updates.
+        Hang,
+    }
+
+    impl Reply {
+        /// A successful response with `result`.
+        pub fn ok(result: impl Into<serde_json::Value>) -> Self {
+            let body = serde_json::json!({ "ok": true, "result": result.into() });
+            Self::Body(body.to_string().into())
+        }
+
+        pub fn raw(body: impl Into<bytes::Bytes>) -> Self {
+            Self::Body(body.into())
+        }
+    }
+
+    type Respond = dyn Fn(&Call, usize) -> Reply + Send + Sync;
+
+    /// A scripted [`BotClient`]: records every request and answers it with
+    /// `respond(call, number of earlier calls)`.
+    pub(crate) struct MockClient {
+        calls: Mutex<Vec<Call>>,
+        respond: Box<Respond>,
+    }
+
+    impl MockClient {
+        pub fn new(respond: impl Fn(&Call, usize) -> Reply + Send + Sync + 'static) -> Arc<Self> {
+            Arc::new(Self {
+                calls: Mutex::default(),
+                respond: Box::new(respond),
+            })
+        }
+
+        /// Answers every request with `true`.
+        #[cfg(feature = "webhook")]
+        pub fn ok() -> Arc<Self> {
+            Self::new(|_, _| Reply::ok(true))
+        }
+
+        /// Serves updates 7 and 8 on the first call, then long-polls forever.
+        pub fn polling() -> Arc<Self> {
+            Self::new(|call, n| match n {
+                0 => Reply::ok(serde_json::json!([{ "update_id": 7 }, { "update_id": 8 }])),
+                _ if call.body["timeout"].as_i64().unwrap_or(0) > 0 => Reply::Hang,
+                _ => Reply::ok(serde_json::json!([])),
+            })
+        }
+
+        /// A bot with token `123:TOKEN` talking to this client.
+        pub fn bot(self: &Arc<Self>) -> Bot {
+            Bot::with_client("123:TOKEN", "https://api.telegram.org", Arc::clone(self)).unwrap()
+        }
+
+        pub fn calls(&self) -> MutexGuard<'_, Vec<Call>> {
+            self.calls.lock().unwrap()
+        }
+
+        /// The `offset` of every call so far, as sent by `getUpdates`.
+        pub fn offsets(&self) -> Vec<i64> {
+            let calls = self.calls();
+            calls
+                .iter()
+                .map(|c| c.body["offset"].as_i64().unwrap_or(0))
+                .collect()
+        }
+
+        /// The methods called so far, in order.
+        pub fn methods(&self) -> Vec<String> {
+            self.calls().iter().map(|c| c.method.clone()).collect()
+        }
+
+        async fn handle(&self, call: Call) -> Result<bytes::Bytes, BotError> {
+            let reply = {
+                let mut calls = self.calls();
+                let reply = (self.respond)(&call, calls.len());
+                calls.push(call);
+                reply
+            };
+            match reply {
+                Reply::Body(body) => Ok(body),
+                Reply::Hang => std::future::pending().await,
+            }
+        }
+    }
+
+    impl fmt::Debug for MockClient {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("MockClient")
+                .field("calls", &self.calls().len())
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[async_trait]
+    impl BotClient for MockClient {
+        async fn post_json(
+            &self,
+            url: &str,
+            body: serde_json::Value,
+        ) -> Result<bytes::Bytes, BotError> {
+            self.handle(Call {
+                url: url.to_string(),
+                method: method_name(url).to_string(),
+                body,
+                form: None,
+            })
+            .await
+        }
+
+        async fn post_form(
+            &self,
+            url: &str,
+            parts: Vec<FormPart>,
+        ) -> Result<bytes::Bytes, BotError> {
+            let body = parts
+                .iter()
+                .filter_map(|p| match &p.body {
+                    FormBody::Text(t) => {
+                        let value: serde_json::Value =
+                            serde_json::from_str(t).unwrap_or_else(|_| t.as_str().into());
+                        Some((p.name.clone(), value))
+                    }
+                    _ => None,
+                })
+                .collect();
+            self.handle(Call {
+                url: url.to_string(),
+                method: method_name(url).to_string(),
+                body,
+                form: Some(parts),
+            })
+            .await
+        }
+
+        async fn get(&self, url: &str) -> Result<bytes::Bytes, BotError> {
+            self.handle(Call {
+                url: url.to_string(),
+                method: String::new(),
+                body: serde_json::Value::Null,
+                form: None,
+            })
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod multipart_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::{
+        client::{BotClient, FormBody, FormPart},
+        types::{InputMediaPhoto, InputMediaVideo},
+        Bot, InputFile, InputMedia,
+    };
+    use std::sync::Arc;
+
+    fn recording_bot() -> (Bot, Arc<MockClient>) {
+        let client = MockClient::new(|_, _| Reply::ok(serde_json::json!([])));
+        (client.bot(), client)
+    }
+
+    /// The parts of the last request, `None` if it was sent as JSON.
+    fn last_form(client: &MockClient) -> Option<Vec<FormPart>> {
+        client.calls().pop()?.form
+    }
+
+    fn text_part<'a>(parts: &'a [FormPart], name: &str) -> &'a str {
+        match parts.iter().find(|p| p.name == name).map(|p| &p.body) {
+            Some(FormBody::Text(t)) => t,
+            other => panic!("expected text part {name}, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn media_group_uploads_memory_files() {
+        let (bot, client) = recording_bot();
+        let media = vec![
+            InputMedia::Photo(InputMediaPhoto {
+                r#type: "photo".into(),
+                media: InputFile::memory("a.jpg", vec![1u8, 2, 3]).into(),
+                ..Default::default()
+            }),
+            InputMedia::Video(InputMediaVideo {
+                r#type: "video".into(),
+                media: InputFile::memory("a.mp4", vec![4u8]).into(),
+                thumbnail: Some(InputFile::memory("a.jpg", vec![5u8]).into()),
+                ..Default::default()
+            }),
+        ];
+        bot.send_media_group(1i64, media, None).await.unwrap();
+
+        let parts = last_form(&client).expect("multipart request");
+        let json: serde_json::Value = serde_json::from_str(text_part(&parts, "media")).unwrap();
+        assert_eq!(json[0]["media"], "attach://file0");
+        assert_eq!(json[1]["media"], "attach://file1");
+        assert_eq!(json[1]["thumbnail"], "attach://file2");
+
+        // Same file name twice still gets two distinct parts.
+        for (name, filename, data) in [
+            ("file0", "a.jpg", &[1u8, 2, 3][..]),
+            ("file1", "a.mp4", &[4u8][..]),
+            ("file2", "a.jpg", &[5u8][..]),
+        ] {
+            match parts.iter().find(|p| p.name == name).map(|p| &p.body) {
+                Some(FormBody::Bytes {
+                    filename: f,
+                    data: d,
+                    ..
+                }) => {
+                    assert_eq!(f, filename);
+                    assert_eq!(&d[..], data);
+                }
+                other => panic!("expected file part {name}, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn media_group_without_files_uses_json() {
+        let (bot, client) = recording_bot();
+        let media = vec![InputMedia::Photo(InputMediaPhoto {
+            r#type: "photo".into(),
+            media: "AgACAgIAAxkBAAI".into(),
+            ..Default::default()
+        })];
+        bot.send_media_group(1i64, media, None).await.unwrap();
+        assert_eq!(client.calls().len(), 1);
+        assert!(last_form(&client).is_none());
+    }
+
+    #[tokio::test]
+    async fn send_document_uploads_file_and_thumbnail_param() {
+        use crate::gen_methods::SendDocumentParams;
+        let (bot, client) = recording_bot();
+        let params = SendDocumentParams::new().thumbnail(InputFile::memory("thumb.jpg", vec![9u8]));
+        let _ = bot
+            .send_document(1i64, InputFile::memory("doc.pdf", vec![8u8]), Some(params))
+            .await;
+
+        let parts = last_form(&client).expect("multipart request");
+        assert_eq!(text_part(&parts, "document"), "attach://file0");
+        assert_eq!(text_part(&parts, "thumbnail"), "attach://file1");
+        assert_eq!(
+            parts.iter().filter(|p| p.name.starts_with("file")).count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn sticker_set_uploads_nested_stickers() {
+        use crate::types::InputSticker;
+        let (bot, client) = recording_bot();
+        let sticker = |name: &str| InputSticker {
+            sticker: InputFile::memory(name, vec![0u8]).into(),
+            format: "static".into(),
+            emoji_list: vec!["🦀".into()],
+            mask_position: None,
+            keywords: None,
+        };
+        let _ = bot
+            .create_new_sticker_set(
+                1,
+                "crabs_by_bot",
+                "Crabs",
+                vec![sticker("a.png"), sticker("b.png")],
+                None,
+            )
+            .await;
+
+        let parts = last_form(&client).expect("multipart request");
+        let json: serde_json::Value = serde_json::from_str(text_part(&parts, "stickers")).unwrap();
+        assert_eq!(json[0]["sticker"], "attach://file0");
+        assert_eq!(json[1]["sticker"], "attach://file1");
+    }
+
+    #[tokio::test]
+    async fn path_and_stream_files_become_streaming_parts() {
+        let (bot, client) = recording_bot();
+        let media = vec![
+            InputMedia::Video(InputMediaVideo {
+                r#type: "video".into(),
+                media: InputFile::path("/archive/big.mp4").into(),
+                ..Default::default()
+            }),
+            InputMedia::Video(InputMediaVideo {
+                r#type: "video".into(),
+                media: InputFile::stream("live.webm", &b"abc"[..], Some(3)).into(),
+                ..Default::default()
+            }),
+        ];
+        bot.send_media_group(1i64, media, None).await.unwrap();
+
+        let parts = last_form(&client).expect("multipart request");
+        match parts.iter().find(|p| p.name == "file0").map(|p| &p.body) {
+            Some(FormBody::Path {
+                filename,
+                mime,
+                path,
+            }) => {
+                assert_eq!(filename, "big.mp4");
+                assert_eq!(mime, "video/mp4");
+                assert_eq!(path, std::path::Path::new("/archive/big.mp4"));
+            }
+            other => panic!("expected path part, got {other:?}"),
+        }
+        match parts.iter().find(|p| p.name == "file1").map(|p| &p.body) {
+            Some(FormBody::Stream { filename, len, .. }) => {
+                assert_eq!(filename, "live.webm");
+                assert_eq!(*len, Some(3));
+            }
+            other => panic!("expected stream part, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn stream_can_only_be_taken_once() {
+        let file = InputFile::stream("a.bin", &b"x"[..], None);
+        let copy = file.clone();
+        assert_eq!(file, copy);
+        let InputFile::Stream { reader, .. } = copy else {
+            unreachable!()
+        };
+        assert!(reader.take().is_some());
+        assert!(file.to_form_body().is_err());
+    }
+
+    #[tokio::test]
+    async fn sending_a_consumed_stream_again_fails() {
+        let (bot, client) = recording_bot();
+        let file = InputFile::stream("a.bin", &b"x"[..], None);
+        // The mock's result isn't a Message; only the request matters here.
+        let _ = bot.send_document(1i64, file.clone(), None).await;
+        assert!(last_form(&client).is_some());
+
+        let err = bot.send_document(1i64, file, None).await.unwrap_err();
+        assert!(err.to_string().contains("already"), "{err}");
+        // Nothing was sent, neither as multipart nor as JSON with a null file.
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reqwest_client_streams_path_and_reader() {
+        use crate::client::ReqwestClient;
+
+        let path = std::env::temp_dir().join(format!("tgbotrs-upload-{}.txt", std::process::id()));
+        tokio::fs::write(&path, b"from-disk").await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route(
+            "/upload",
+            axum::routing::post(|body: axum::body::Bytes| async move { body }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = ReqwestClient::with_timeout(std::time::Duration::from_secs(5)).unwrap();
+        let parts = vec![
+            FormPart {
+                name: "a".into(),
+                body: InputFile::path(&path).to_form_body().unwrap().unwrap(),
+            },
+            FormPart {
+                name: "b".into(),
+                body: InputFile::stream("b.txt", &b"from-reader"[..], None)
+                    .to_form_body()
+                    .unwrap()
+                    .unwrap(),
+            },
+        ];
+        let echoed = client
+            .post_form(&format!("http://{addr}/upload"), parts)
+            .await
+            .unwrap();
+        tokio::fs::remove_file(&path).await.ok();
+
+        let echoed = String::from_utf8_lossy(&echoed);
+        assert!(echoed.contains("from-disk"));
+        assert!(echoed.contains("from-reader"));
+        assert!(echoed.contains("filename=\"b.txt\""));
+    }
+
+    #[test]
+    fn memory_file_outside_collector_keeps_filename() {
+        let v = serde_json::to_value(InputFile::memory("pic.png", vec![0u8])).unwrap();
+        assert_eq!(v, "attach://pic.png");
+    }
+}
+
+#[cfg(test)]
+mod download_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::{types::File, Bot};
+
+    /// Serves `getFile` and one fixture file; relies on the default `get_stream`.
+    fn bot() -> Bot {
+        let client = MockClient::new(|call, _| match call.method.as_str() {
+            "getFile" => Reply::ok(serde_json::json!({
+                "file_id": "f1",
+                "file_unique_id": "u1",
+                "file_path": "photos/file_1.jpg",
+            })),
+            method => {
+                assert_eq!(method, "", "unexpected call");
+                assert_eq!(
+                    call.url,
+                    "https://api.telegram.org/file/bot123:TOKEN/photos/file_1.jpg"
+                );
+                Reply::raw(&b"fixture-bytes"[..])
+            }
+        });
+        client.bot()
+    }
+
+    fn file(path: Option<&str>) -> File {
+        File {
+            file_id: "f1".into(),
+            file_unique_id: "u1".into(),
+            file_size: None,
+            file_path: path.map(Into::into),
+        }
+    }
+
+    #[tokio::test]
+    async fn download_by_id_goes_through_client() {
+        let data = bot().download_file_by_id("f1").await.unwrap();
+        assert_eq!(&data[..], b"fixture-bytes");
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_reports_progress() {
+        let mut out = Vec::new();
+        let mut seen = Vec::new();
+        let n = bot()
+            .download_file_with_progress(
+                &file(Some("photos/file_1.jpg")),
+                &mut out,
+                |done, total| seen.push((done, total)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(n, 13);
+        assert_eq!(out, b"fixture-bytes");
+        assert_eq!(seen.last(), Some(&(13, Some(13))));
+    }
+
+    #[tokio::test]
+    async fn download_reads_local_server_paths_from_disk() {
+        let path = std::env::temp_dir().join(format!("tgbotrs-local-{}.bin", std::process::id()));
+        tokio::fs::write(&path, b"on-disk").await.unwrap();
+        let data = bot().download_file(&file(path.to_str())).await.unwrap();
+        tokio::fs::remove_file(&path).await.ok();
+        assert_eq!(&data[..], b"on-disk");
+    }
+
+    #[tokio::test]
+    async fn download_without_path_errors() {
+        assert!(bot().download_file(&file(None)).await.is_err());
+    }
+}
+
+// Retry policy wired through Bot
+
+#[cfg(test)]
+mod retry_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::{Bot, BotError, InputFile, RetryPolicy};
+    use std::{sync::Arc, time::Duration};
+
+    const FLOOD: &str = r#"{"ok":false,"error_code":429,"description":"Too Many Requests","parameters":{"retry_after":0}}"#;
+    const BAD_GATEWAY: &str = r#"{"ok":false,"error_code":502,"description":"Bad Gateway"}"#;
+
+    /// Answers the first `fail` requests with `error`, then succeeds.
+    fn flaky_bot(fail: usize, error: &'static str) -> (Bot, Arc<MockClient>) {
+        let client = MockClient::new(move |_, n| {
+            if n < fail {
+                Reply::raw(error)
+            } else {
+                Reply::ok(true)
+            }
+        });
+        let bot = client
+            .bot()
+            .with_retry(RetryPolicy::new().base_delay(Duration::from_millis(1)));
+        (bot, client)
+    }
+
+    #[tokio::test]
+    async fn flood_wait_is_retried() {
+        let (bot, client) = flaky_bot(2, FLOOD);
+        let ok: bool = bot
+            .call_api("sendChatAction", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert!(ok);
+        assert_eq!(client.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn server_error_on_send_is_not_retried() {
+        let (bot, client) = flaky_bot(1, BAD_GATEWAY);
+        let res: Result<bool, _> = bot.call_api("sendMessage", serde_json::json!({})).await;
+        assert!(matches!(res, Err(BotError::Api { code: 502, .. })));
+        assert_eq!(client.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn multipart_retries_only_replayable_parts() {
+        let (bot, client) = flaky_bot(1, FLOOD);
+        let file = InputFile::memory("a.txt", b"abc".to_vec());
+        let ok: bool = bot
+            .call_api_with_file("sendDocument", Default::default(), "document", file.into())
+            .await
+            .unwrap();
+        assert!(ok);
+        assert_eq!(client.calls().len(), 2);
+
+        let (bot, client) = flaky_bot(1, FLOOD);
+        let file = InputFile::stream("a.txt", &b"abc"[..], Some(3));
+        let res: Result<bool, _> = bot
+            .call_api_with_file("sendDocument", Default::default(), "document", file.into())
+            .await;
+        assert!(matches!(res, Err(BotError::Api { code: 429, .. })));
+        assert_eq!(client.calls().len(), 1);
+    }
+}
+
+// Token redaction
+
+#[cfg(test)]
+mod redaction_tests {
+    use crate::{Bot, BotError, ReqwestClient};
+
+    const TOKEN: &str = "123456:AAHdqTcvCH1vGWJxfSeofSAs0K5PALDsaw";
+
+    #[test]
+    fn bot_debug_masks_token() {
+        let bot = Bot::new_unverified(TOKEN).unwrap();
+        let out = format!("{bot:?}");
+        assert!(!out.contains("AAHdq"), "{out}");
+        assert!(out.contains("123456:***"));
+    }
+
+    #[tokio::test]
+    async fn http_errors_do_not_leak_token() {
+        let client = ReqwestClient::with_timeout(std::time::Duration::from_secs(5)).unwrap();
+        let bot = Bot::with_client(TOKEN, "http://127.0.0.1:1", client).unwrap();
+        let err = bot.get_me().await.unwrap_err();
+        assert!(matches!(err, BotError::Http(_)));
+        for out in [err.to_string(), format!("{err:?}")] {
+            assert!(!out.contains("AAHdq"), "{out}");
+        }
+        assert!(err.to_string().contains("bot123456:***"));
+    }
+
+    #[test]
+    fn http_errors_keep_their_source() {
+        use std::error::Error;
+
+        let err = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let err = BotError::from(err);
+        assert!(matches!(err, BotError::Http(_)));
+        assert!(err.source().is_some());
+    }
+
+    #[tokio::test]
+    async fn unsupported_downloads_do_not_leak_token() {
+        use crate::{
+            client::{BotClient, FormPart},
+            types::File,
+        };
+        use async_trait::async_trait;
+
+        /// Relies on the default `get`.
+        #[derive(Debug)]
+        struct NoGet;
+
+        #[async_trait]
+        impl BotClient for NoGet {
+            async fn post_json(
+                &self,
+                _: &str,
+                _: serde_json::Value,
+            ) -> Result<bytes::Bytes, BotError> {
+                unreachable!()
+            }
+            async fn post_form(&self, _: &str, _: Vec<FormPart>) -> Result<bytes::Bytes, BotError> {
+                unreachable!()
+            }
+        }
+
+        let bot = Bot::with_client(TOKEN, "https://api.telegram.org", NoGet).unwrap();
+        let file: File = serde_json::from_value(serde_json::json!({
+            "file_id": "f",
+            "file_unique_id": "u",
+            "file_path": "documents/a.txt",
+        }))
+        .unwrap();
+        let err = bot.download_file(&file).await.unwrap_err();
+        for out in [err.to_string(), format!("{err:?}")] {
+            assert!(!out.contains("AAHdq"), "{out}");
+        }
+    }
+
+    #[test]
+    fn redact_token_only_masks_tokens() {
+        use crate::error::redact_token;
+        assert_eq!(
+            redact_token("https://api.telegram.org/bot1:abc/getMe"),
+            "https://api.telegram.org/bot1:***/getMe"
+        );
+        assert_eq!(redact_token(&format!("/hook/{TOKEN}")), "/hook/123456:***");
+        assert_eq!(redact_token("at 12:30 today"), "at 12:30 today");
+    }
+}
+
+// Group-to-supergroup migration
+
+#[cfg(test)]
+mod migration_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::{
+        framework::{handler::Handler, ChatMigrationHandler, Context},
+        types::Update,
+        BotError, InputFile,
+    };
+    use std::sync::{Arc, Mutex};
+
+    const OLD: i64 = -123;
+    const NEW: i64 = -100123;
+
+    /// Rejects requests to the old group with a migration error.
+    fn migrated_client() -> Arc<MockClient> {
+        MockClient::new(|call, _| {
+            if call.body["chat_id"] == OLD {
+                Reply::raw(format!(
+                    r#"{{"ok":false,"error_code":400,"description":"Bad Request: group chat was upgraded to a supergroup chat","parameters":{{"migrate_to_chat_id":{NEW}}}}}"#
+                ))
+            } else {
+                Reply::ok(true)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn migration_errors_surface_by_default() {
+        let res: Result<bool, _> = migrated_client()
+            .bot()
+            .call_api("sendChatAction", serde_json::json!({ "chat_id": OLD }))
+            .await;
+        assert!(matches!(
+            res,
+            Err(BotError::Api {
+                migrate_to_chat_id: Some(NEW),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn auto_migration_resends_and_notifies() {
+        let client = migrated_client();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let bot = client
+            .bot()
+            .on_chat_migrated(move |old, new| sink.lock().unwrap().push((old, new)));
+
+        let ok: bool = bot
+            .call_api("sendChatAction", serde_json::json!({ "chat_id": OLD }))
+            .await
+            .unwrap();
+        assert!(ok);
+
+        let file = InputFile::memory("a.txt", b"abc".to_vec());
+        let mut body = serde_json::Map::new();
+        body.insert("chat_id".into(), OLD.into());
+        let ok: bool = bot
+            .call_api_with_file("sendDocument", body, "document", file.into())
+            .await
+            .unwrap();
+        assert!(ok);
+
+        assert_eq!(*seen.lock().unwrap(), [(OLD, NEW), (OLD, NEW)]);
+        let ids: Vec<_> = client
+            .calls()
+            .iter()
+            .map(|c| c.body["chat_id"].clone())
+            .collect();
+        assert_eq!(ids, [OLD, NEW, OLD, NEW]);
+    }
+
+    #[tokio::test]
+    async fn migration_handler_matches_service_messages() {
+        let update: Update = serde_json::from_value(serde_json::json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 5,
+                "date": 0,
+                "chat": { "id": NEW, "type": "supergroup" },
+                "migrate_from_chat_id": OLD
+            }
+        }))
+        .unwrap();
+        let msg = update.message.as_ref().unwrap();
+        assert_eq!(msg.chat_migration(), Some((OLD, NEW)));
+
+        let handler = ChatMigrationHandler::new(|_, _| async { Ok(()) });
+        assert!(handler.check_update(&Context::new(update)));
+        let plain: Update = serde_json::from_value(serde_json::json!({
+            "update_id": 2,
+            "message": { "message_id": 6, "date": 0, "chat": { "id": NEW, "type": "supergroup" } }
+        }))
+        .unwrap();
+        assert!(!handler.check_update(&Context::new(plain)));
+    }
+}
+
+// Graceful shutdown
+
+#[cfg(test)]
+mod shutdown_tests {
+    use super::mock::MockClient;
+    use crate::{
+        framework::{handler::Handler, Context, HandlerResult},
+        Bot, CommitPolicy, Dispatcher, DispatcherOpts, Poller, UpdateHandler, Updater,
+    };
+    use async_trait::async_trait;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    fn poll_bot() -> (Bot, Arc<MockClient>) {
+        let client = MockClient::polling();
+        (client.bot(), client)
+    }
+
+    /// The `(offset, timeout)` of the last `getUpdates` call.
+    fn last_poll(client: &MockClient) -> Option<(i64, i64)> {
+        let calls = client.calls();
+        let body = &calls.last()?.body;
+        Some((
+            body["offset"].as_i64().unwrap_or(0),
+            body["timeout"].as_i64().unwrap_or(0),
+        ))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poller_confirms_offset_and_drains_handlers() {
+        let (bot, client) = poll_bot();
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&done);
+        let handler: UpdateHandler = Box::new(move |_, update| {
+            let sink = Arc::clone(&sink);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                sink.lock().unwrap().push(update.update_id);
+            })
+        });
+        let poller = Poller::new(bot, handler);
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        let mut done = done.lock().unwrap().clone();
+        done.sort();
+        assert_eq!(done, [7, 8]);
+        assert_eq!(last_poll(&client), Some((9, 0)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poller_aborts_handlers_after_deadline() {
+        let (bot, _) = poll_bot();
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&finished);
+        let handler: UpdateHandler = Box::new(move |_, _| {
+            let flag = Arc::clone(&flag);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                flag.store(true, Ordering::SeqCst);
+            })
+        });
+        let poller = Poller::new(bot, handler).shutdown_timeout(Duration::from_secs(1));
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let stopped_at = tokio::time::Instant::now();
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(stopped_at.elapsed(), Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    struct SlowHandler(Arc<Mutex<Vec<i64>>>);
+
+    #[async_trait]
+    impl Handler for SlowHandler {
+        fn name(&self) -> &str {
+            "slow"
+        }
+        fn check_update(&self, _: &Context) -> bool {
+            true
+        }
+        async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            self.0.lock().unwrap().push(ctx.update.update_id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn updater_waits_for_dispatcher_tasks() {
+        let (bot, client) = poll_bot();
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let mut dp = Dispatcher::new(DispatcherOpts::default());
+        dp.add_handler(SlowHandler(Arc::clone(&done)));
+        let updater = Updater::new(bot, dp);
+        let stop = updater.stop_handle();
+        let run = tokio::spawn(updater.start_polling());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(done.lock().unwrap().len(), 2);
+        assert_eq!(last_poll(&client), Some((9, 0)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn updater_aborts_dispatcher_tasks_at_one_deadline() {
+        let (bot, _) = poll_bot();
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let mut dp = Dispatcher::new(DispatcherOpts::default());
+        dp.add_handler(SlowHandler(Arc::clone(&done)));
+        let updater = Updater::new(bot, dp)
+            .commit_policy(CommitPolicy::AfterHandling)
+            .shutdown_timeout(Duration::from_secs(1));
+        let stop = updater.stop_handle();
+        let run = tokio::spawn(updater.start_polling());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let stopped_at = tokio::time::Instant::now();
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(stopped_at.elapsed(), Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert!(done.lock().unwrap().is_empty());
+    }
+}
+
+// Offset stores
+
+#[cfg(test)]
+mod offset_store_tests {
+    use super::mock::MockClient;
+    use crate::{CommitPolicy, InMemoryOffsetStore, OffsetStore, Poller, UpdateHandler};
+    use std::{sync::Arc, time::Duration};
+
+    fn sleeping_handler(secs: u64) -> UpdateHandler {
+        Box::new(move |_, _| Box::pin(tokio::time::sleep(Duration::from_secs(secs))))
+    }
+
+    fn poller(handler: UpdateHandler, store: &InMemoryOffsetStore) -> (Poller, Arc<MockClient>) {
+        let client = MockClient::polling();
+        let poller = Poller::new(client.bot(), handler).offset_store(store.clone());
+        (poller, client)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poller_resumes_from_stored_offset() {
+        let store = InMemoryOffsetStore::new();
+        store.save(5).await.unwrap();
+        let (poller, client) = poller(sleeping_handler(0), &store);
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(client.offsets()[..2], [5, 9]);
+        assert_eq!(store.get(), Some(9));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn after_receipt_commits_before_handlers_finish() {
+        let store = InMemoryOffsetStore::new();
+        let (poller, _) = poller(sleeping_handler(60), &store);
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.get(), Some(9));
+        stop.stop();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn after_handling_waits_for_the_batch() {
+        let store = InMemoryOffsetStore::new();
+        let (poller, client) = poller(sleeping_handler(2), &store);
+        let poller = poller.commit_policy(CommitPolicy::AfterHandling);
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(store.get(), None);
+        assert_eq!(client.offsets(), [0]);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(store.get(), Some(9));
+        assert_eq!(client.offsets(), [0, 9]);
+        stop.stop();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn after_handling_keeps_aborted_batch_uncommitted() {
+        let store = InMemoryOffsetStore::new();
+        let (poller, client) = poller(sleeping_handler(60), &store);
+        let poller = poller
+            .commit_policy(CommitPolicy::AfterHandling)
+            .shutdown_timeout(Duration::from_secs(1));
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        // Neither Telegram nor the store was told about updates 7 and 8.
+        assert_eq!(store.get(), None);
+        assert_eq!(client.offsets(), [0]);
+    }
+}
+
+// Webhook cleanup
+
+#[cfg(test)]
+mod webhook_cleanup_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::{Bot, Poller, UpdateHandler};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    /// `getUpdates` fails with 409 while a webhook is set and long-polls
+    /// forever otherwise.
+    fn webhook_bot(webhook_set: bool) -> (Bot, Arc<MockClient>) {
+        let webhook_set = AtomicBool::new(webhook_set);
+        let client = MockClient::new(move |call, _| match call.method.as_str() {
+            "deleteWebhook" => {
+                webhook_set.store(false, Ordering::SeqCst);
+                Reply::ok(true)
+            }
+            "setWebhook" => {
+                webhook_set.store(true, Ordering::SeqCst);
+                Reply::ok(true)
+            }
+            "getUpdates" if webhook_set.load(Ordering::SeqCst) => Reply::raw(
+                r#"{"ok":false,"error_code":409,"description":"Conflict: can't use getUpdates method while webhook is active; use deleteWebhook to delete the webhook first"}"#,
+            ),
+            "getUpdates" if call.body["timeout"].as_i64().unwrap_or(0) > 0 => Reply::Hang,
+            _ => Reply::ok(serde_json::json!([])),
+        });
+        (client.bot(), client)
+    }
+
+    fn noop() -> UpdateHandler {
+        Box::new(|_, _| Box::pin(async {}))
+    }
+
+    #[tokio::test]
+    async fn poller_fails_fast_on_conflict() {
+        let (bot, client) = webhook_bot(true);
+        let err = Poller::new(bot, noop()).start().await.unwrap_err();
+        assert!(err.is_conflict());
+        assert_eq!(client.methods(), ["getUpdates"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poller_deletes_webhook_first() {
+        let (bot, client) = webhook_bot(true);
+        let poller = Poller::new(bot, noop()).delete_webhook(false);
+        let stop = poller.stop_handle();
+        let run = tokio::spawn(poller.start());
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(client.methods()[..2], ["deleteWebhook", "getUpdates"]);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn webhook_server_deletes_webhook_on_shutdown() {
+        let (bot, client) = webhook_bot(false);
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = crate::WebhookServer::new(bot, noop())
+            .port(port)
+            .delete_webhook_on_shutdown();
+        let stop = server.stop_handle();
+        let run = tokio::spawn(async move { server.start("https://example.com").await });
+
+        while client.calls().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(client.methods(), ["setWebhook", "deleteWebhook"]);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn webhook_monitor_registers_again_after_drift() {
+        use std::{sync::Mutex, time::Duration};
+
+        // A fake Telegram keeping the registered webhook settings.
+        let webhook = Arc::new(Mutex::new(serde_json::Value::Null));
+        let registered = Arc::clone(&webhook);
+        let client = MockClient::new(move |call, _| {
+            let mut webhook = registered.lock().unwrap();
+            match call.method.as_str() {
+                "setWebhook" => {
+                    *webhook = call.body.clone();
+                    Reply::ok(true)
+                }
+                "getWebhookInfo" => Reply::ok(serde_json::json!({
+                    "url": webhook["url"],
+                    "has_custom_certificate": false,
+                    "pending_update_count": 3,
+                    "last_error_date": 1700000000,
+                    "last_error_message": "Connection refused",
+                    "allowed_updates": webhook["allowed_updates"],
+                })),
+                _ => Reply::ok(true),
+            }
+        });
+        let set_calls = || {
+            let calls = client.calls();
+            calls
+                .iter()
+                .filter(|c| c.method == "setWebhook")
+                .map(|c| c.body.clone())
+                .collect::<Vec<_>>()
+        };
+
+        async fn wait_until(cond: impl Fn() -> bool) {
+            while !cond() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+
+        let bot = client.bot();
+        let pending = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&pending);
+        let server = crate::WebhookServer::new(bot, noop())
+            .host([127, 0, 0, 1])
+            .port(0)
+            .allowed_updates(vec!["message".into(), "callback_query".into()])
+            .drop_pending_updates()
+            .monitor(Duration::from_millis(20))
+            .on_webhook_info(move |info| seen.lock().unwrap().push(info.pending_update_count));
+        let stop = server.stop_handle();
+        let run = tokio::spawn(async move { server.start("https://example.com/").await });
+
+        let sets = || set_calls().len();
+        wait_until(|| pending.lock().unwrap().len() >= 2).await;
+        assert_eq!(sets(), 1);
+        assert!(pending.lock().unwrap().iter().all(|&n| n == 3));
+
+        // Someone points the webhook elsewhere.
+        webhook.lock().unwrap()["url"] = "https://elsewhere.example.com/hook".into();
+        wait_until(|| sets() == 2).await;
+        // Same allowed updates in a different order are no drift.
+        webhook.lock().unwrap()["allowed_updates"] =
+            serde_json::json!(["callback_query", "message"]);
+        let checked = pending.lock().unwrap().len();
+        wait_until(|| pending.lock().unwrap().len() >= checked + 2).await;
+        assert_eq!(sets(), 2);
+        webhook.lock().unwrap()["allowed_updates"] = serde_json::json!(["message"]);
+        wait_until(|| sets() == 3).await;
+
+        stop.stop();
+        run.await.unwrap().unwrap();
+        let calls = set_calls();
+        assert!(calls
+            .iter()
+            .all(|c| c["url"] == "https://example.com/webhook"));
+        assert_eq!(calls[0]["drop_pending_updates"], true);
+        assert!(calls[1].get("drop_pending_updates").is_none());
+    }
+}
+
+// Update streams
+
+#[cfg(test)]
+mod stream_tests {
+    use super::mock::{MockClient, Reply};
+    use crate::Bot;
+    use futures_util::StreamExt;
+    use std::sync::Arc;
+
+    /// Answers `getUpdates` from a script of raw responses.
+    fn scripted(responses: Vec<&'static str>) -> (Bot, Arc<MockClient>) {
+        let client = MockClient::new(move |_, n| Reply::raw(responses[n]));
+        (client.bot(), client)
+    }
+
+    #[tokio::test]
+    async fn polls_only_when_consumer_pulls() {
+        let (bot, client) = scripted(vec![
+            r#"{"ok":true,"result":[{"update_id":1},{"update_id":2}]}"#,
+            r#"{"ok":true,"result":[{"update_id":3}]}"#,
+        ]);
+        let mut updates = std::pin::pin!(bot.update_stream(None));
+
+        assert_eq!(updates.next().await.unwrap().unwrap().update_id, 1);
+        assert_eq!(updates.next().await.unwrap().unwrap().update_id, 2);
+        assert_eq!(client.offsets(), [0]);
+
+        assert_eq!(updates.next().await.unwrap().unwrap().update_id, 3);
+        assert_eq!(client.offsets(), [0, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn errors_are_yielded_and_flood_waits_slept() {
+        let (bot, client) = scripted(vec![
+            r#"{"ok":false,"error_code":429,"description":"Too Many Requests","parameters":{"retry_after":5}}"#,
+            r#"{"ok":false,"error_code":502,"description":"Bad Gateway"}"#,
+            r#"{"ok":true,"result":[{"update_id":9}]}"#,
+        ]);
+        let start = tokio::time::Instant::now();
+        let mut updates = std::pin::pin!(bot.update_stream(None));
+
+        let err = updates.next().await.unwrap().unwrap_err();
+        assert!(err.is_api_error_code(502));
+        assert_eq!(start.elapsed().as_secs(), 5);
+
+        assert_eq!(updates.next().await.unwrap().unwrap().update_id, 9);
+        assert_eq!(start.elapsed().as_secs(), 8);
+        assert_eq!(client.offsets().len(), 3);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn webhook_stream_receives_posted_updates() {
+        let (bot, _) = scripted(vec![r#"{"ok":true,"result":true}"#]);
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (server, updates) = crate::WebhookServer::with_stream(bot, 1);
+        tokio::spawn(server.port(port).start("https://example.com"));
+        let mut updates = std::pin::pin!(updates);
+
+        let url = format!("http://127.0.0.1:{port}/webhook");
+        let http = reqwest::Client::new();
+        let send = |id: i64| {
+            http.post(&url)
+                .json(&serde_json::json!({ "update_id": id }))
+                .send()
+        };
+        let mut status = None;
+        for _ in 0..50 {
+            if let Ok(resp) = send(1).await {
+                status = Some(resp.status());
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(status, Some(reqwest::StatusCode::OK));
+        assert_eq!(updates.next().await.unwrap().update_id, 1);
+    }
+}
+
+// Mountable webhook routers
+
+#[cfg(test)]
+#[cfg(feature = "webhook")]
+mod webhook_router_tests {
+    use super::mock::MockClient;
+    use crate::{Bot, WebhookServer};
+    use axum::{routing::get, Router};
+    use futures_util::StreamExt;
+    use std::sync::{Arc, Mutex};
+
+    /// Serve `app` on an ephemeral local port and return its base URL.
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    fn bot() -> Bot {
+        Bot::new_unverified("123456789:fake_token_for_testing").unwrap()
+    }
+
+    #[tokio::test]
+    async fn webhook_router_mounts_next_to_other_routes() {
+        let (webhook, updates) = WebhookServer::with_stream(bot(), 4);
+        let webhook = webhook.path("/telegram").secret_token("s3cret");
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .merge(webhook.router());
+        let base = serve(app).await;
+        let http = reqwest::Client::new();
+
+        let health = http.get(format!("{base}/health")).send().await.unwrap();
+        assert_eq!(health.text().await.unwrap(), "ok");
+
+        let post = |secret: &'static str| {
+            http.post(format!("{base}/telegram"))
+                .header("x-telegram-bot-api-secret-token", secret)
+                .json(&serde_json::json!({ "update_id": 5 }))
+                .send()
+        };
+        assert_eq!(post("wrong").await.unwrap().status(), 403);
+        assert_eq!(post("s3cret").await.unwrap().status(), 200);
+
+        let mut updates = std::pin::pin!(updates);
+        assert_eq!(updates.next().await.unwrap().update_id, 5);
+    }
+
+    #[cfg(feature = "bot-mapping")]
+    #[tokio::test]
+    async fn bot_mapping_router_nests_under_prefix() {
+        use crate::{
+            framework::{handler::Handler, Context, HandlerResult},
+            BotMapping, Dispatcher, DispatcherOpts,
+        };
+        use async_trait::async_trait;
+
+        struct Record(Arc<Mutex<Vec<i64>>>);
+
+        #[async_trait]
+        impl Handler for Record {
+            fn name(&self) -> &str {
+                "record"
+            }
+            fn check_update(&self, _: &Context) -> bool {
+                true
+            }
+            async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+                self.0.lock().unwrap().push(ctx.update.update_id);
+                Ok(())
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut dp = Dispatcher::new(DispatcherOpts::default());
+        dp.add_handler(Record(Arc::clone(&seen)));
+        let map = BotMapping::new();
+        map.add_bot("/a", bot(), dp);
+        let base = serve(Router::new().nest("/tg", map.router())).await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{base}/tg/a"))
+            .json(&serde_json::json!({ "update_id": 9 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        while seen.lock().unwrap().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert_eq!(*seen.lock().unwrap(), [9]);
+    }
+
+    #[cfg(feature = "bot-mapping")]
+    #[tokio::test]
+    async fn bot_mapping_checks_secrets_and_registers_webhooks() {
+        use crate::{BotMapping, Dispatcher, DispatcherOpts};
+
+        let client = MockClient::ok();
+        let map = BotMapping::new().webhook_base_url("https://bots.example.com/tg/");
+        map.add_bot_with_secret(
+            "/a",
+            client.bot(),
+            Dispatcher::new(DispatcherOpts::default()),
+            "s3cret",
+        );
+
+        map.register_webhooks().await.unwrap();
+        {
+            let calls = client.calls();
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].body["url"], "https://bots.example.com/tg/a");
+            assert_eq!(calls[0].body["secret_token"], "s3cret");
+        }
+
+        let base = serve(Router::new().nest("/tg", map.router())).await;
+        let http = reqwest::Client::new();
+        let post = |secret: Option<&'static str>| {
+            let mut req = http
+                .post(format!("{base}/tg/a"))
+                .json(&serde_json::json!({ "update_id": 1 }));
+            if let Some(secret) = secret {
+                req = req.header("x-telegram-bot-api-secret-token", secret);
+            }
+            req.send()
+        };
+        assert_eq!(post(None).await.unwrap().status(), 403);
+        assert_eq!(post(Some("wrong")).await.unwrap().status(), 403);
+        assert_eq!(post(Some("s3cret")).await.unwrap().status(), 200);
+    }
+
+    #[cfg(feature = "bot-mapping")]
+    #[tokio::test]
+    async fn bot_mapping_adds_and_removes_bots_while_serving() {
+        use crate::{BotMapping, Dispatcher, DispatcherOpts};
+
+        let map = BotMapping::new().webhook_base_url("https://bots.example.com");
+        let base = serve(map.router()).await;
+        let http = reqwest::Client::new();
+        let post = || {
+            http.post(format!("{base}/b"))
+                .json(&serde_json::json!({ "update_id": 1 }))
+                .send()
+        };
+        assert_eq!(post().await.unwrap().status(), 404);
+
+        let client = MockClient::ok();
+        let dp = || Dispatcher::new(DispatcherOpts::default());
+        map.register_bot("/b", client.bot(), dp(), None)
+            .await
+            .unwrap();
+        assert_eq!(map.paths(), ["b"]);
+        assert_eq!(post().await.unwrap().status(), 200);
+
+        assert!(map.replace_dispatcher("b", dp()));
+        assert_eq!(post().await.unwrap().status(), 200);
+
+        assert!(map.unregister_bot("/b").await.unwrap());
+        assert_eq!(post().await.unwrap().status(), 404);
+        assert!(!map.unregister_bot("/b").await.unwrap());
+        assert_eq!(client.methods(), ["setWebhook", "deleteWebhook"]);
+    }
+
+    #[cfg(feature = "bot-mapping")]
+    #[tokio::test]
+    async fn bot_mapping_swaps_dispatchers_without_cutting_off_handlers() {
+        use crate::{
+            framework::{handler::Handler, Context, HandlerResult},
+            BotMapping, Dispatcher, DispatcherOpts,
+        };
+        use async_trait::async_trait;
+        use std::time::Duration;
+
+        /// Logs when it starts and finishes handling an update.
+        struct Slow(Arc<Mutex<Vec<&'static str>>>);
+
+        #[async_trait]
+        impl Handler for Slow {
+            fn name(&self) -> &str {
+                "slow"
+            }
+            fn check_update(&self, _: &Context) -> bool {
+                true
+            }
+            async fn handle_update(&self, _: Bot, _: Context) -> HandlerResult {
+                self.0.lock().unwrap().push("started");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                self.0.lock().unwrap().push("finished");
+                Ok(())
+            }
+        }
+
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut dp = Dispatcher::new(DispatcherOpts::default());
+        dp.add_handler(Slow(Arc::clone(&log)));
+        let map = BotMapping::new();
+        map.add_bot("/a", bot(), dp);
+        let base = serve(map.router()).await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{base}/a"))
+            .json(&serde_json::json!({ "update_id": 1 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        while log.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // The old dispatcher goes away while its handler is still running.
+        assert!(map.replace_dispatcher("/a", Dispatcher::new(DispatcherOpts::default())));
+        assert!(map.remove_bot("/a"));
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(*log.lock().unwrap(), ["started", "finished"]);
+    }
+
+    #[tokio::test]
+    async fn webhook_replies_in_response_or_falls_back_to_api_call() {
+        use crate::{ReplyHandler, WebhookReply};
+        use std::time::Duration;
+
+        let client = MockClient::ok();
+        let bot = client.bot();
+        // Update 1 is answered at once, update 2 only after the deadline.
+        let handler: ReplyHandler = Box::new(|_, update| {
+            Box::pin(async move {
+                let id = update.update_id;
+                if id == 2 {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                }
+                Some(WebhookReply::send_message(id, format!("reply {id}")))
+            })
+        });
+        let server =
+            WebhookServer::with_replies(bot, handler).reply_deadline(Duration::from_millis(100));
+        let base = serve(server.router()).await;
+        let http = reqwest::Client::new();
+        let post = |id: i64| {
+            http.post(format!("{base}/webhook"))
+                .json(&serde_json::json!({ "update_id": id }))
+                .send()
+        };
+
+        let fast = post(1).await.unwrap();
+        assert_eq!(fast.status(), 200);
+        let body: serde_json::Value = fast.json().await.unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "method": "sendMessage", "chat_id": 1, "text": "reply 1" })
+        );
+
+        let slow = post(2).await.unwrap();
+        assert_eq!(slow.status(), 200);
+        assert!(slow.text().await.unwrap().is_empty());
+        while client.calls().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, "sendMessage");
+        assert_eq!(calls[0].body["text"], "reply 2");
+    }
+
+    /// Like [`serve`], but with the peer address the IP allowlist needs.
+    async fn serve_with_peer(app: Router) -> String {
+        use std::net::SocketAddr;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn webhook_rejects_malformed_and_oversized_bodies() {
+        let (webhook, updates) = WebhookServer::with_stream(bot(), 4);
+        let base = serve(webhook.max_body_size(64).router()).await;
+        let http = reqwest::Client::new();
+        let post = |body: String| {
+            http.post(format!("{base}/webhook"))
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+        };
+
+        assert_eq!(post("{not json".into()).await.unwrap().status(), 400);
+        assert_eq!(post(r#"{"message":1}"#.into()).await.unwrap().status(), 400);
+        let big = format!(r#"{{"update_id":1,"pad":"{}"}}"#, "x".repeat(100));
+        assert_eq!(post(big).await.unwrap().status(), 413);
+        assert_eq!(
+            post(r#"{"update_id":3}"#.into()).await.unwrap().status(),
+            200
+        );
+
+        let mut updates = std::pin::pin!(updates);
+        assert_eq!(updates.next().await.unwrap().update_id, 3);
+    }
+
+    #[tokio::test]
+    async fn webhook_allowlist_checks_source_ip_behind_trusted_proxy() {
+        let http = reqwest::Client::new();
+        let post = |base: &str, forwarded: Option<&'static str>| {
+            let mut req = http
+                .post(format!("{base}/webhook"))
+                .json(&serde_json::json!({ "update_id": 1 }));
+            if let Some(ip) = forwarded {
+                req = req.header("x-forwarded-for", ip);
+            }
+            req.send()
+        };
+
+        let (direct, _updates) = WebhookServer::with_stream(bot(), 4);
+        let base = serve_with_peer(direct.allow_network([10, 0, 0, 0], 8).router()).await;
+        assert_eq!(post(&base, None).await.unwrap().status(), 403);
+        // The header is ignored unless the peer is a trusted proxy.
+        let spoofed = post(&base, Some("10.1.2.3")).await.unwrap();
+        assert_eq!(spoofed.status(), 403);
+
+        let (proxied, _updates) = WebhookServer::with_stream(bot(), 4);
+        let proxied = proxied
+            .allow_telegram_networks()
+            .trusted_proxy([127, 0, 0, 1], 32);
+        let base = serve_with_peer(proxied.router()).await;
+        let from = |ip| post(&base, Some(ip));
+        assert_eq!(from("149.154.167.99").await.unwrap().status(), 200);
+        assert_eq!(from("91.108.6.1").await.unwrap().status(), 200);
+        assert_eq!(from("203.0.113.9").await.unwrap().status(), 403);
+        assert_eq!(post(&base, None).await.unwrap().status(), 403);
+
+        // Without connect info the peer is unknown, so nothing gets through.
+        let (mounted, _updates) = WebhookServer::with_stream(bot(), 4);
+        let base = serve(mounted.allow_telegram_networks().router()).await;
+        assert_eq!(
+            post(&base, Some("149.154.167.99")).await.unwrap().status(),
+            403
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_health_reports_registration() {
+        let http = reqwest::Client::new();
+        let probe = |base: String| http.get(format!("{base}/healthz")).send();
+
+        let (idle, _updates) = WebhookServer::with_stream(bot(), 4);
+        let resp = probe(serve(idle.router()).await).await.unwrap();
+        assert_eq!(resp.status(), 503);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "webhook_registered": false }));
+
+        // Neither the secret token nor the allowlist applies to the probe.
+        let (webhook, _updates) = WebhookServer::with_stream(MockClient::ok().bot(), 4);
+        let webhook = webhook
+            .secret_token("s3cret")
+            .allow_network([10, 0, 0, 0], 8)
+            .health_path("/live");
+        webhook.register("https://bots.example.com").await.unwrap();
+        let base = serve(webhook.router()).await;
+        let resp = http.get(format!("{base}/live")).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "webhook_registered": true }));
+        assert_eq!(probe(base).await.unwrap().status(), 404);
+
+        let (quiet, _updates) = WebhookServer::with_stream(bot(), 4);
+        let base = serve(quiet.without_health_check().router()).await;
+        assert_eq!(probe(base).await.unwrap().status(), 404);
+    }
+
+    #[cfg(feature = "webhook-tls")]
+    #[tokio::test]
+    async fn tls_webhook_uploads_self_signed_certificate() {
+        use crate::{client::FormBody, WebhookTls};
+
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_pem = generated.cert.pem();
+        let tls = WebhookTls::from_pem(cert_pem.clone(), generated.key_pair.serialize_pem());
+
+        let client = MockClient::ok();
+        let bot = client.bot();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (server, updates) = WebhookServer::with_stream(bot, 1);
+        let server = server
+            .host([127, 0, 0, 1])
+            .port(port)
+            .tls(tls.self_signed());
+        let stop = server.stop_handle();
+        let run = tokio::spawn(async move { server.start("https://localhost").await });
+
+        let https = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+        let url = format!("https://localhost:{port}/webhook");
+        let mut status = None;
+        for _ in 0..50 {
+            let sent = https
+                .post(&url)
+                .json(&serde_json::json!({ "update_id": 3 }))
+                .send()
+                .await;
+            if let Ok(resp) = sent {
+                status = Some(resp.status());
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(status, Some(reqwest::StatusCode::OK));
+        let mut updates = std::pin::pin!(updates);
+        assert_eq!(updates.next().await.unwrap().update_id, 3);
+
+        stop.stop();
+        run.await.unwrap().unwrap();
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, "setWebhook");
+        let parts = calls[0].form.as_ref().expect("multipart request");
+        assert_eq!(calls[0].body["certificate"], "attach://file0");
+        match parts.iter().find(|p| p.name == "file0").map(|p| &p.body) {
+            Some(FormBody::Bytes { data, .. }) => assert_eq!(&data[..], cert_pem.as_bytes()),
+            other => panic!("expected certificate part, got {other:?}"),
+        }
+    }
+}
+
+// Update deduplication
+
+#[cfg(test)]
+mod dedup_tests {
+    use crate::{
+        framework::{handler::Handler, Context, HandlerResult},
+        types::Update,
+        Bot, BotError, DedupStore, Dispatcher, DispatcherOpts, InMemoryDedupStore,
+    };
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn update(id: i64) -> Update {
+        serde_json::from_value(serde_json::json!({ "update_id": id })).unwrap()
+    }
+
+    fn bot(id: i64) -> Bot {
+        Bot::new_unverified(format!("{id}:fake_token_for_testing")).unwrap()
+    }
+
+    struct Record(Arc<Mutex<Vec<i64>>>);
+
+    #[async_trait]
+    impl Handler for Record {
+        fn name(&self) -> &str {
+            "record"
+        }
+        fn check_update(&self, _: &Context) -> bool {
+            true
+        }
+        async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+            self.0.lock().unwrap().push(ctx.update.update_id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn in_memory_window_is_bounded_by_size_and_age() {
+        let store = InMemoryDedupStore::new(2, Duration::from_secs(60));
+        assert!(store.first_seen(1, 10).await.unwrap());
+        assert!(!store.first_seen(1, 10).await.unwrap());
+        // update_ids are per bot.
+        assert!(store.first_seen(2, 10).await.unwrap());
+        assert_eq!(store.len(), 2);
+
+        // A third update pushes out the oldest.
+        assert!(store.first_seen(1, 11).await.unwrap());
+        assert_eq!(store.len(), 2);
+        assert!(store.first_seen(1, 10).await.unwrap());
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(store.first_seen(1, 11).await.unwrap());
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dispatcher_drops_duplicates_per_bot() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let store = InMemoryDedupStore::default();
+        let mut dp = Dispatcher::new(DispatcherOpts::default().dedup(store.clone()));
+        dp.add_handler(Record(Arc::clone(&seen)));
+
+        for id in [1, 2, 1, 2, 3] {
+            dp.dispatch_and_wait(bot(100), update(id)).await;
+        }
+        dp.dispatch_and_wait(bot(200), update(1)).await;
+        assert_eq!(*seen.lock().unwrap(), [1, 2, 3, 1]);
+        assert_eq!(store.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn failing_store_lets_updates_through() {
+        struct Down;
+
+        #[async_trait]
+        impl DedupStore for Down {
+            async fn first_seen(&self, _: i64, _: i64) -> Result<bool, BotError> {
+                Err(BotError::Other("store unavailable".into()))
+            }
+            async fn forget(&self, _: i64, _: i64) -> Result<(), BotError> {
+                Err(BotError::Other("store unavailable".into()))
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut dp = Dispatcher::new(DispatcherOpts::default().dedup(Down));
+        dp.add_handler(Record(Arc::clone(&seen)));
+        dp.dispatch_and_wait(bot(100), update(1)).await;
+        dp.dispatch_and_wait(bot(100), update(1)).await;
+        assert_eq!(*seen.lock().unwrap(), [1, 1]);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn webhook_acknowledges_redeliveries_without_forwarding() {
+        use crate::WebhookServer;
+        use futures_util::StreamExt;
+
+        let (server, updates) = WebhookServer::with_stream(bot(100), 4);
+        let app = server.dedup(InMemoryDedupStore::default()).router();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let http = reqwest::Client::new();
+        for id in [7, 7, 8] {
+            let resp = http
+                .post(format!("http://{addr}/webhook"))
+                .json(&serde_json::json!({ "update_id": id }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+        let updates: Vec<_> = updates.take(2).map(|u| u.update_id).collect().await;
+        assert_eq!(updates, [7, 8]);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn webhook_redelivery_after_503_is_not_a_duplicate() {
+        use crate::WebhookServer;
+
+        let store = InMemoryDedupStore::default();
+        let (server, updates) = WebhookServer::with_stream(bot(100), 4);
+        let app = server.dedup(store.clone()).router();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        // Nobody consumes the stream, so the update can't be handed off.
+        drop(updates);
+
+        let http = reqwest::Client::new();
+        for _ in 0..2 {
+            let resp = http
+                .post(format!("http://{addr}/webhook"))
+                .json(&serde_json::json!({ "update_id": 7 }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 503);
+        }
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn forgotten_updates_are_new_again() {
+        let store = InMemoryDedupStore::new(2, Duration::from_secs(60));
+        assert!(store.first_seen(1, 10).await.unwrap());
+        store.forget(1, 10).await.unwrap();
+        assert!(store.first_seen(1, 11).await.unwrap());
+        assert!(store.first_seen(1, 10).await.unwrap());
+        // The forgotten entry doesn't take a slot or evict the new one early.
+        assert!(!store.first_seen(1, 11).await.unwrap());
+        assert!(!store.first_seen(1, 10).await.unwrap());
+    }
+}
+
+// Dispatcher middleware
+
+#[cfg(test)]
+mod middleware_tests {
+    use crate::{
+        framework::{handler::Handler, Context, HandlerResult},
+        types::Update,
+        Bot, DispatchOutcome, Dispatcher, DispatcherOpts, Middleware, Next,
+    };
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn update(id: i64) -> Update {
+        serde_json::from_value(serde_json::json!({ "update_id": id })).unwrap()
+    }
+
+    fn bot() -> Bot {
+        Bot::new_unverified("123456789:fake_token_for_testing").unwrap()
+    }
+
+    /// Logs around the rest of the chain and keeps the outcomes it saw.
+    struct Around {
+        name: &'static str,
+        log: Log,
+        outcomes: Arc<Mutex<Vec<DispatchOutcome>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for Around {
+        async fn handle(&self, bot: Bot, mut ctx: Context, next: Next<'_>) -> DispatchOutcome {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{}:before", self.name));
+            ctx.data.insert("seen_by".into(), self.name.into());
+            let outcome = next.run(bot, ctx).await;
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{}:after", self.name));
+            self.outcomes.lock().unwrap().push(outcome.clone());
+            outcome
+        }
+    }
+
+    /// Skips updates with an even id.
+    struct SkipEven;
+
+    #[async_trait]
+    impl Middleware for SkipEven {
+        async fn handle(&self, bot: Bot, ctx: Context, next: Next<'_>) -> DispatchOutcome {
+            if ctx.update.update_id % 2 == 0 {
+                return DispatchOutcome::skipped();
+            }
+            next.run(bot, ctx).await
+        }
+    }
+
+    /// Logs `seen_by` from the context; fails on update 3.
+    struct Record(Log);
+
+    #[async_trait]
+    impl Handler for Record {
+        fn name(&self) -> &str {
+            "record"
+        }
+        fn check_update(&self, ctx: &Context) -> bool {
+            ctx.update.update_id != 5
+        }
+        async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+            let seen_by = ctx.data.get("seen_by").cloned().unwrap_or_default();
+            self.0.lock().unwrap().push(format!("handler:{seen_by}"));
+            if ctx.update.update_id == 3 {
+                return Err("boom".into());
+            }
+            Ok(())
+        }
+    }
+
+    fn dispatcher(log: &Log, outcomes: &Arc<Mutex<Vec<DispatchOutcome>>>) -> Dispatcher {
+        let around = |name| Around {
+            name,
+            log: Arc::clone(log),
+            outcomes: Arc::clone(outcomes),
+        };
+        let opts = DispatcherOpts::default()
+            .middleware(around("outer"))
+            .middleware(SkipEven)
+            .middleware(around("inner"));
+        let mut dp = Dispatcher::new(opts);
+        dp.add_handler_to_group(Record(Arc::clone(log)), 3);
+        dp
+    }
+
+    #[tokio::test]
+    async fn middlewares_wrap_handlers_in_order() {
+        let log = Log::default();
+        let outcomes = Arc::default();
+        let dp = dispatcher(&log, &outcomes);
+
+        dp.dispatch_and_wait(bot(), update(1)).await;
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "outer:before",
+                "inner:before",
+                "handler:inner",
+                "inner:after",
+                "outer:after"
+            ]
+        );
+        let outcome = outcomes.lock().unwrap()[0].clone();
+        assert_eq!(outcome.handlers, [(3, "record".to_string())]);
+        assert!(outcome.is_handled() && outcome.is_ok() && !outcome.skipped);
+    }
+
+    #[tokio::test]
+    async fn middleware_can_skip_updates() {
+        let log = Log::default();
+        let outcomes = Arc::default();
+        let dp = dispatcher(&log, &outcomes);
+
+        dp.dispatch_and_wait(bot(), update(2)).await;
+        assert_eq!(*log.lock().unwrap(), ["outer:before", "outer:after"]);
+        assert_eq!(*outcomes.lock().unwrap(), [DispatchOutcome::skipped()]);
+    }
+
+    #[tokio::test]
+    async fn outcome_reports_errors_and_unhandled_updates() {
+        let log = Log::default();
+        let outcomes: Arc<Mutex<Vec<DispatchOutcome>>> = Arc::default();
+        let dp = dispatcher(&log, &outcomes);
+
+        dp.dispatch_and_wait(bot(), update(3)).await;
+        dp.dispatch_and_wait(bot(), update(5)).await;
+        let outcomes = outcomes.lock().unwrap();
+        // Inner and outer middleware see the same outcome.
+        assert_eq!(outcomes[0], outcomes[1]);
+        assert_eq!(outcomes[0].errors, ["boom"]);
+        assert!(outcomes[0].is_handled());
+        assert!(!outcomes[2].is_handled() && outcomes[2].is_ok());
+    }
+}
+
+// Typed state and extensions
+
+#[cfg(test)]
+mod extensions_tests {
+    use crate::{
+        framework::{handler::Handler, Context, HandlerResult},
+        types::Update,
+        Bot, DispatchOutcome, Dispatcher, DispatcherOpts, Extensions, Middleware, Next,
+    };
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        greeting: &'static str,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Locale(&'static str);
+
+    #[test]
+    fn extensions_hold_one_value_per_type() {
+        let mut ext = Extensions::new();
+        assert!(ext.get::<Locale>().is_none());
+        ext.insert(Locale("en"));
+        ext.insert(Locale("de"));
+        ext.insert(7u32);
+        assert_eq!(ext.len(), 2);
+        assert_eq!(ext.get::<Locale>(), Some(&Locale("de")));
+        assert_eq!(ext.get::<u32>(), Some(&7));
+        assert!(ext.get::<u64>().is_none());
+
+        let copy = ext.clone();
+        assert!(ext.remove::<Locale>());
+        assert!(!ext.remove::<Locale>());
+        assert!(copy.contains::<Locale>());
+    }
+
+    /// Attaches a locale to updates with an odd id.
+    struct DetectLocale;
+
+    #[async_trait]
+    impl Middleware for DetectLocale {
+        async fn handle(&self, bot: Bot, mut ctx: Context, next: Next<'_>) -> DispatchOutcome {
+            if ctx.update.update_id % 2 == 1 {
+                ctx.extensions.insert(Locale("de"));
+            }
+            next.run(bot, ctx).await
+        }
+    }
+
+    struct Greet(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Handler for Greet {
+        fn name(&self) -> &str {
+            "greet"
+        }
+        fn check_update(&self, ctx: &Context) -> bool {
+            ctx.state::<Config>().is_some()
+        }
+        async fn handle_update(&self, _: Bot, ctx: Context) -> HandlerResult {
+            let config = ctx.state::<Config>().unwrap();
+            let locale = ctx.extension::<Locale>().map_or("en", |l| l.0);
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{} ({locale})", config.greeting));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn handlers_read_state_and_middleware_extensions() {
+        let out = Arc::new(Mutex::new(vec![]));
+        let opts = DispatcherOpts::default()
+            .state(Config { greeting: "hello" })
+            .middleware(DetectLocale);
+        let mut dp = Dispatcher::new(opts);
+        dp.add_handler(Greet(Arc::clone(&out)));
+
+        let bot = Bot::new_unverified("123456789:fake_token_for_testing").unwrap();
+        for id in [1, 2] {
+            let update: Update =
+                serde_json::from_value(serde_json::json!({ "update_id": id })).unwrap();
+            dp.dispatch_and_wait(bot.clone(), update).await;
+        }
+        assert_eq!(*out.lock().unwrap(), ["hello (de)", "hello (en)"]);
+
+        // Contexts built outside a dispatcher have no state.
+        let update: Update = serde_json::from_value(serde_json::json!({ "update_id": 3 })).unwrap();
+        assert!(Context::new(update).state::<Config>().is_none());
+    }
+}
@@ -35,12 +35,21 @@
 //! }
 //! ```
 
-use std::sync::Arc;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tracing::warn;
 
 use crate::{
     framework::{Dispatcher, DispatcherOpts},
+    offset_store::{CommitPolicy, OffsetStore},
     types::Update,
-    Bot, BotError, Poller, UpdateHandler,
+    Bot, BotError, Poller, StopHandle, UpdateHandler,
 };
 
 /// Combines a [`Bot`] and a [`Dispatcher`] into a single, self-contained
@@ -48,7 +57,7 @@ use crate::{
 ///
 /// Use [`Updater::start_polling`] for long-polling or
 /// [`Updater::start_webhook`] for webhook mode (requires the `webhook`
-/// feature). Both methods block until the process exits.
+/// feature). Polling runs until stopped through [`Updater::stop_handle`].
 pub struct Updater {
     bot: Bot,
     dispatcher: Arc<Dispatcher>,
@@ -58,12 +67,28 @@ pub struct Updater {
     poll_limit: i64,
     /// Which update types to receive (empty = all).
     allowed_updates: Vec<String>,
+    /// Stops polling or the webhook server.
+    stop: StopHandle,
+    /// How long to wait for in-flight handlers after a stop (default 10 s).
+    shutdown_timeout: Duration,
+    /// Where polling offsets are persisted.
+    offset_store: Option<Box<dyn OffsetStore>>,
+    /// When polling offsets are committed.
+    commit_policy: CommitPolicy,
+    /// Delete the webhook before polling; the value is `drop_pending_updates`.
+    delete_webhook: Option<bool>,
+    /// Webhook listen address (default 0.0.0.0).
+    #[allow(dead_code)]
+    webhook_host: IpAddr,
     /// Webhook port (default 8080).
     #[allow(dead_code)]
     webhook_port: u16,
     /// Webhook secret token.
     #[allow(dead_code)]
     webhook_secret: Option<String>,
+    /// Call `deleteWebhook` when the webhook server stops.
+    #[allow(dead_code)]
+    delete_webhook_on_shutdown: bool,
 }
 
 impl Updater {
@@ -84,8 +109,15 @@ impl Updater {
             poll_timeout: 30,
             poll_limit: 100,
             allowed_updates: vec![],
+            stop: StopHandle::new(),
+            shutdown_timeout: Duration::from_secs(10),
+            offset_store: None,
+            commit_policy: CommitPolicy::default(),
+            delete_webhook: None,
+            webhook_host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             webhook_port: 8080,
             webhook_secret: None,
+            delete_webhook_on_shutdown: false,
         }
     }
 
@@ -108,6 +140,47 @@ impl Updater {
         self
     }
 
+    /// Set how long to wait for in-flight handlers after a stop (default: 10 s).
+    /// Handlers still running after that are aborted.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Persist polling offsets in `store`; see [`crate::offset_store`].
+    pub fn offset_store(mut self, store: impl OffsetStore + 'static) -> Self {
+        self.offset_store = Some(Box::new(store));
+        self
+    }
+
+    /// When polling offsets are committed (default: after receipt). With
+    /// [`CommitPolicy::AfterHandling`] an update counts as handled once the
+    /// dispatcher has finished with it.
+    pub fn commit_policy(mut self, policy: CommitPolicy) -> Self {
+        self.commit_policy = policy;
+        self
+    }
+
+    /// Delete any registered webhook before [`Updater::start_polling`],
+    /// optionally dropping pending updates. Without this a leftover webhook
+    /// makes polling fail with `409 Conflict`.
+    pub fn delete_webhook(mut self, drop_pending_updates: bool) -> Self {
+        self.delete_webhook = Some(drop_pending_updates);
+        self
+    }
+
+    /// Handle that stops [`Updater::start_polling`] and
+    /// [`Updater::start_webhook`]; see [`crate::shutdown`].
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Set the webhook listening address (default: 0.0.0.0).
+    pub fn webhook_host(mut self, host: impl Into<IpAddr>) -> Self {
+        self.webhook_host = host.into();
+        self
+    }
+
     /// Set the webhook listening port (default: 8080).
     pub fn webhook_port(mut self, port: u16) -> Self {
         self.webhook_port = port;
@@ -120,38 +193,87 @@ impl Updater {
         self
     }
 
+    /// Delete the webhook when [`Updater::start_webhook`] stops (default:
+    /// off). Leave it off for rolling deploys, where the replacement has
+    /// already registered the same webhook.
+    pub fn delete_webhook_on_shutdown(mut self) -> Self {
+        self.delete_webhook_on_shutdown = true;
+        self
+    }
+
     /// Expose the inner [`Bot`] (e.g. to add handlers before starting).
     pub fn bot(&self) -> &Bot {
         &self.bot
     }
 
     // Start modes
-    /// Start long-polling. Blocks until the process exits or an unrecoverable
-    /// error occurs.
+    /// Start long-polling. Blocks until stopped through
+    /// [`Updater::stop_handle`].
     ///
     /// Each received update is dispatched through the [`Dispatcher`] in a
-    /// spawned Tokio task (same semantics as [`Dispatcher::dispatch`]).
+    /// spawned Tokio task (same semantics as [`Dispatcher::dispatch`]). On
+    /// stop, waits up to the shutdown timeout for those tasks to finish and
+    /// aborts the rest.
     pub async fn start_polling(self) -> Result<(), BotError> {
         let dp = Arc::clone(&self.dispatcher);
         let bot = self.bot.clone();
+        let wait = self.commit_policy == CommitPolicy::AfterHandling;
 
         // Build an UpdateHandler closure that calls into the Dispatcher.
         let handler: UpdateHandler = Box::new(move |bot: Bot, update: Update| {
             let dp2 = Arc::clone(&dp);
             Box::pin(async move {
-                dp2.dispatch(bot, update);
+                if wait {
+                    dp2.dispatch_and_wait(bot, update).await;
+                } else {
+                    dp2.dispatch(bot, update);
+                }
             })
         });
 
-        Poller::new(bot, handler)
+        let stop = self.stop.clone();
+        let mut poller = Poller::new(bot, handler)
             .timeout(self.poll_timeout)
             .limit(self.poll_limit)
             .allowed_updates(self.allowed_updates)
-            .start()
+            .with_stop_handle(self.stop)
+            .shutdown_timeout(self.shutdown_timeout)
+            .commit_policy(self.commit_policy);
+        if let Some(store) = self.offset_store {
+            poller = poller.offset_store(store);
+        }
+        if let Some(drop_pending) = self.delete_webhook {
+            poller = poller.delete_webhook(drop_pending);
+        }
+
+        // The shutdown timeout counts from the stop request and covers both
+        // the poller's handlers and the dispatcher's own tasks.
+        let mut polling = pin!(poller.start());
+        let stopped_at = tokio::select! {
+            result = &mut polling => {
+                result?;
+                Instant::now()
+            }
+            () = stop.stopped() => {
+                let at = Instant::now();
+                polling.await?;
+                at
+            }
+        };
+        let idle = self.dispatcher.wait_idle();
+        if tokio::time::timeout_at(stopped_at + self.shutdown_timeout, idle)
             .await
+            .is_err()
+        {
+            warn!("shutdown timeout reached, aborting dispatcher tasks");
+            self.dispatcher.abort_all();
+        }
+        Ok(())
     }
 
-    /// Start webhook mode. Blocks until the process exits.
+    /// Start webhook mode. Blocks until stopped through
+    /// [`Updater::stop_handle`]. The webhook stays registered unless
+    /// [`Updater::delete_webhook_on_shutdown`] is set.
     ///
     /// Requires the `webhook` feature flag.
     #[cfg(feature = "webhook")]
@@ -160,6 +282,7 @@ impl Updater {
 
         let dp = Arc::clone(&self.dispatcher);
         let bot = self.bot.clone();
+        let host = self.webhook_host;
         let port = self.webhook_port;
         let secret = self.webhook_secret.clone();
 
@@ -170,7 +293,13 @@ impl Updater {
             })
         });
 
-        let mut server = WebhookServer::new(bot, handler).port(port);
+        let mut server = WebhookServer::new(bot, handler)
+            .host(host)
+            .port(port)
+            .with_stop_handle(self.stop);
+        if self.delete_webhook_on_shutdown {
+            server = server.delete_webhook_on_shutdown();
+        }
         if let Some(s) = secret {
             server = server.secret_token(s);
         }
@@ -219,8 +348,10 @@ mod tests {
                 .poll_timeout(60)
                 .poll_limit(50)
                 .allowed_updates(vec!["message".into()])
+                .shutdown_timeout(Duration::from_secs(5))
                 .webhook_port(8443)
-                .webhook_secret("abc");
+                .webhook_secret("abc")
+                .delete_webhook_on_shutdown();
         }
         let _ = check; // unused fn lint suppression
     }
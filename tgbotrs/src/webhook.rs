@@ -12,6 +12,52 @@
 //! Telegram retries if the server returns non-2xx or takes too long, so the
 //! handler is always spawned - the endpoint returns `200 OK` immediately.
 //!
+//! To consume updates as a [`Stream`] instead, use
+//! [`WebhookServer::with_stream`].
+//!
+//! To answer an update in the HTTP response itself, saving a round trip, use
+//! [`WebhookServer::with_replies`]: the handler returns an optional
+//! [`WebhookReply`], which is sent back to Telegram if the handler finishes
+//! within [`WebhookServer::reply_deadline`] and as a normal API call
+//! otherwise.
+//!
+//! With the `webhook-tls` feature the server can terminate HTTPS itself, see
+//! [`WebhookServer::tls`]. A self-signed certificate is uploaded to Telegram
+//! when the webhook is registered.
+//!
+//! For production deployments the server can restrict requests to
+//! Telegram's networks ([`WebhookServer::allow_telegram_networks`]), cap the
+//! request body size ([`WebhookServer::max_body_size`]) and answer liveness
+//! probes on `/healthz` ([`WebhookServer::health_path`]). Bodies that are not
+//! a valid update are rejected with `400 Bad Request`.
+//!
+//! [`WebhookServer::monitor`] periodically checks `getWebhookInfo` while the
+//! server runs, logs delivery errors reported by Telegram and registers the
+//! webhook again if someone else changed it.
+//!
+//! To serve the webhook from an existing axum application, call
+//! [`WebhookServer::register`] once and mount [`WebhookServer::router`]
+//! instead of calling [`WebhookServer::start`]:
+//!
+//! ```rust,no_run
+//! use axum::{routing::get, Router};
+//! use tgbotrs::{Bot, UpdateHandler, WebhookServer};
+//!
+//! # async fn example(bot: Bot, handler: UpdateHandler) -> Result<(), Box<dyn std::error::Error>> {
+//! let webhook = WebhookServer::new(bot, handler)
+//!     .path("/telegram")
+//!     .secret_token("my_secret");
+//! webhook.register("https://yourdomain.com").await?;
+//!
+//! let app = Router::new()
+//!     .route("/health", get(|| async { "ok" }))
+//!     .merge(webhook.router());
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+//! axum::serve(listener, app).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -37,24 +83,193 @@
 //! }
 //! ```
 
+use crate::dedup::{self, DedupStore};
 use crate::polling::UpdateHandler;
-use crate::types::Update;
-use crate::{Bot, BotError};
+use crate::shutdown::StopHandle;
+use crate::types::{Update, WebhookInfo};
+use crate::{error::redact_token, Bot, BotError, ChatId};
+
+use futures_util::{stream, Stream};
+use tokio::sync::{mpsc, oneshot};
 
 use axum::{
-    extract::State,
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, Request, State},
     http::{HeaderMap, StatusCode},
-    routing::post,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
-use std::net::SocketAddr;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::{error, info, warn};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// A handler that may answer its update with a [`WebhookReply`].
+pub type ReplyHandler = Box<
+    dyn Fn(Bot, Update) -> Pin<Box<dyn Future<Output = Option<WebhookReply>> + Send>> + Send + Sync,
+>;
+
+/// Receives every [`WebhookInfo`] fetched by [`WebhookServer::monitor`].
+type WebhookInfoFn = dyn Fn(&WebhookInfo) + Send + Sync;
+
+/// A single Bot API call returned in the webhook HTTP response.
+///
+/// Telegram doesn't report whether such a call succeeded, so use it for
+/// fire-and-forget answers like `sendMessage` or `answerCallbackQuery`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookReply {
+    pub method: String,
+    /// The method's parameters as a JSON object.
+    pub params: serde_json::Value,
+}
+
+impl WebhookReply {
+    pub fn new(method: impl Into<String>, params: serde_json::Value) -> Self {
+        Self {
+            method: method.into(),
+            params,
+        }
+    }
+
+    pub fn send_message(chat_id: impl Into<ChatId>, text: impl Into<String>) -> Self {
+        Self::new(
+            "sendMessage",
+            serde_json::json!({ "chat_id": chat_id.into(), "text": text.into() }),
+        )
+    }
+
+    pub fn answer_callback_query(callback_query_id: impl Into<String>) -> Self {
+        Self::new(
+            "answerCallbackQuery",
+            serde_json::json!({ "callback_query_id": callback_query_id.into() }),
+        )
+    }
+
+    /// The response body: the parameters plus a `method` field.
+    fn body(self) -> serde_json::Value {
+        let mut body = match self.params {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        };
+        body.insert("method".into(), self.method.into());
+        serde_json::Value::Object(body)
+    }
+}
+
+/// Networks Telegram sends webhook requests from, see
+/// <https://core.telegram.org/bots/webhooks#the-short-version>.
+const TELEGRAM_NETWORKS: [(Ipv4Addr, u8); 2] = [
+    (Ipv4Addr::new(149, 154, 160, 0), 20),
+    (Ipv4Addr::new(91, 108, 4, 0), 22),
+];
 
 struct AppState {
     bot: Bot,
-    handler: Arc<UpdateHandler>,
+    sink: Sink,
     secret_token: Option<String>,
+    reply_deadline: Duration,
+    access: Access,
+    registered: Arc<AtomicBool>,
+    dedup: Option<Arc<dyn DedupStore>>,
+}
+
+/// An IP network in CIDR notation, e.g. `149.154.160.0/20`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    fn new(addr: IpAddr, prefix: u8) -> Self {
+        let canonical = addr.to_canonical();
+        let prefix = match (addr, canonical) {
+            // `::ffff:a.b.c.d/n` covers `a.b.c.d/(n - 96)`.
+            (IpAddr::V6(_), IpAddr::V4(_)) => prefix.saturating_sub(96).min(32),
+            (IpAddr::V4(_), _) => prefix.min(32),
+            (IpAddr::V6(_), _) => prefix.min(128),
+        };
+        Self {
+            addr: canonical,
+            prefix,
+        }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Source-IP restrictions for webhook requests.
+#[derive(Debug, Clone)]
+struct Access {
+    /// Networks allowed to post updates (empty = everyone).
+    allowed: Vec<IpNet>,
+    /// Proxies whose forwarding header is believed.
+    trusted_proxies: Vec<IpNet>,
+    /// Header carrying the client address, e.g. `X-Forwarded-For`.
+    forwarded_header: String,
+}
+
+impl Access {
+    /// The address the request originally came from.
+    ///
+    /// The forwarding header is only consulted when the peer is a trusted
+    /// proxy, and read from the right, skipping further trusted proxies:
+    /// entries to the left of the first untrusted hop can be forged.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let trusted = |ip: IpAddr| self.trusted_proxies.iter().any(|net| net.contains(ip));
+        let mut ip = peer.to_canonical();
+        if !trusted(ip) {
+            return ip;
+        }
+        let hops: Vec<&str> = headers
+            .get_all(self.forwarded_header.as_str())
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect();
+        for hop in hops.into_iter().rev() {
+            let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            ip = hop.to_canonical();
+            if !trusted(ip) {
+                break;
+            }
+        }
+        ip
+    }
+
+    fn allows(&self, ip: IpAddr) -> bool {
+        self.allowed.is_empty() || self.allowed.iter().any(|net| net.contains(ip))
+    }
+}
+
+/// Where received updates go.
+enum Sink {
+    Handler(Arc<UpdateHandler>),
+    Reply(Arc<ReplyHandler>),
+    Channel(mpsc::Sender<Update>),
 }
 
 /// A built-in HTTP server that receives Telegram webhook updates.
@@ -63,7 +278,9 @@ struct AppState {
 /// to switch between long-polling and webhooks.
 pub struct WebhookServer {
     bot: Bot,
-    handler: UpdateHandler,
+    sink: Sink,
+    /// Local address to bind (default: `0.0.0.0`).
+    host: IpAddr,
     /// Local port to bind (default: `8080`).
     port: u16,
     /// URL path Telegram will POST to (default: `"/webhook"`).
@@ -76,19 +293,123 @@ pub struct WebhookServer {
     max_connections: Option<i64>,
     /// Drop pending updates when registering the webhook.
     drop_pending_updates: bool,
+    /// Stops the server.
+    stop: StopHandle,
+    /// Call `deleteWebhook` once the server has stopped.
+    delete_on_shutdown: bool,
+    /// How long a reply handler may take to answer in the response.
+    reply_deadline: Duration,
+    /// Source-IP allowlist and trusted proxies.
+    access: Access,
+    /// Largest accepted request body in bytes (default: 1 MiB).
+    max_body_size: usize,
+    /// Path of the liveness probe (default: `"/healthz"`).
+    health_path: Option<String>,
+    /// Set once `setWebhook` succeeded.
+    registered: Arc<AtomicBool>,
+    /// How often to check `getWebhookInfo` while running.
+    monitor_interval: Option<Duration>,
+    /// Observer for monitored webhook info, e.g. to export metrics.
+    on_webhook_info: Option<Arc<WebhookInfoFn>>,
+    /// Drops redelivered updates.
+    dedup: Option<Arc<dyn DedupStore>>,
+    /// Terminate HTTPS with this certificate.
+    #[cfg(feature = "webhook-tls")]
+    tls: Option<WebhookTls>,
 }
 
 impl WebhookServer {
     pub fn new(bot: Bot, handler: UpdateHandler) -> Self {
+        Self::with_sink(bot, Sink::Handler(Arc::new(handler)))
+    }
+
+    /// Create a server that delivers updates to the returned [`Stream`]
+    /// instead of a handler.
+    ///
+    /// Up to `buffer` updates are queued. When the queue is full the webhook
+    /// request is held open until the consumer catches up, so Telegram slows
+    /// down rather than updates piling up in memory. If the stream is
+    /// dropped, requests are answered with `503` and Telegram redelivers
+    /// them later.
+    ///
+    /// ```rust,no_run
+    /// use futures_util::StreamExt;
+    /// use tgbotrs::{Bot, WebhookServer};
+    ///
+    /// # async fn example(bot: Bot) {
+    /// let (server, updates) = WebhookServer::with_stream(bot, 64);
+    /// tokio::spawn(server.port(8080).start("https://yourdomain.com"));
+    ///
+    /// let mut updates = std::pin::pin!(updates);
+    /// while let Some(update) = updates.next().await {
+    ///     println!("update {}", update.update_id);
+    /// }
+    /// # }
+    /// ```
+    pub fn with_stream(
+        bot: Bot,
+        buffer: usize,
+    ) -> (Self, impl Stream<Item = Update> + Send + 'static) {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        let updates = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|update| (update, rx))
+        });
+        (Self::with_sink(bot, Sink::Channel(tx)), updates)
+    }
+
+    /// Create a server whose handler may answer each update with a
+    /// [`WebhookReply`] in the HTTP response.
+    ///
+    /// The request is held open until the handler finishes or the
+    /// [`reply deadline`](WebhookServer::reply_deadline) passes. A late
+    /// reply is sent as a regular API call instead.
+    ///
+    /// ```rust,no_run
+    /// use tgbotrs::{Bot, ReplyHandler, WebhookReply, WebhookServer};
+    ///
+    /// # async fn example(bot: Bot) -> Result<(), tgbotrs::BotError> {
+    /// let handler: ReplyHandler = Box::new(|_bot, update| {
+    ///     Box::pin(async move {
+    ///         let query = update.callback_query?;
+    ///         Some(WebhookReply::answer_callback_query(query.id))
+    ///     })
+    /// });
+    /// WebhookServer::with_replies(bot, handler)
+    ///     .start("https://yourdomain.com")
+    ///     .await
+    /// # }
+    /// ```
+    pub fn with_replies(bot: Bot, handler: ReplyHandler) -> Self {
+        Self::with_sink(bot, Sink::Reply(Arc::new(handler)))
+    }
+
+    fn with_sink(bot: Bot, sink: Sink) -> Self {
         Self {
             bot,
-            handler,
+            sink,
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             port: 8080,
             path: "/webhook".to_string(),
             secret_token: None,
             allowed_updates: vec![],
             max_connections: None,
             drop_pending_updates: false,
+            stop: StopHandle::new(),
+            delete_on_shutdown: false,
+            reply_deadline: Duration::from_secs(1),
+            access: Access {
+                allowed: vec![],
+                trusted_proxies: vec![],
+                forwarded_header: "x-forwarded-for".to_string(),
+            },
+            max_body_size: 1024 * 1024,
+            health_path: Some("/healthz".to_string()),
+            registered: Arc::new(AtomicBool::new(false)),
+            monitor_interval: None,
+            on_webhook_info: None,
+            dedup: None,
+            #[cfg(feature = "webhook-tls")]
+            tls: None,
         }
     }
 
@@ -100,6 +421,13 @@ impl WebhookServer {
         self
     }
 
+    /// Set the local address to listen on (default: `0.0.0.0`), e.g.
+    /// `127.0.0.1` behind a reverse proxy on the same host.
+    pub fn host(mut self, host: impl Into<IpAddr>) -> Self {
+        self.host = host.into();
+        self
+    }
+
     /// Set the URL path for webhook POSTs (default: `"/webhook"`).
     pub fn path(mut self, path: impl Into<String>) -> Self {
         self.path = path.into();
@@ -132,16 +460,264 @@ impl WebhookServer {
         self
     }
 
+    /// How long to wait for a [`WebhookServer::with_replies`] handler before
+    /// answering the request without a reply (default: 1 s).
+    pub fn reply_deadline(mut self, deadline: Duration) -> Self {
+        self.reply_deadline = deadline;
+        self
+    }
+
+    /// Only accept updates from `addr/prefix`, e.g. `([10, 0, 0, 0], 8)`.
+    /// May be called repeatedly; requests from other addresses get `403`.
+    ///
+    /// The check needs the peer address, which [`WebhookServer::start`]
+    /// provides. When mounting [`WebhookServer::router`] yourself, serve it
+    /// with `into_make_service_with_connect_info::<SocketAddr>()`; without
+    /// it every request is rejected.
+    pub fn allow_network(mut self, addr: impl Into<IpAddr>, prefix: u8) -> Self {
+        self.access.allowed.push(IpNet::new(addr.into(), prefix));
+        self
+    }
+
+    /// Only accept updates from the networks Telegram publishes for
+    /// webhooks, `149.154.160.0/20` and `91.108.4.0/22`.
+    pub fn allow_telegram_networks(self) -> Self {
+        TELEGRAM_NETWORKS
+            .into_iter()
+            .fold(self, |server, (addr, prefix)| {
+                server.allow_network(addr, prefix)
+            })
+    }
+
+    /// Trust the forwarding header of requests from `addr/prefix`, e.g. the
+    /// reverse proxy in front of this server, when checking
+    /// [`allowed networks`](WebhookServer::allow_network).
+    pub fn trusted_proxy(mut self, addr: impl Into<IpAddr>, prefix: u8) -> Self {
+        self.access
+            .trusted_proxies
+            .push(IpNet::new(addr.into(), prefix));
+        self
+    }
+
+    /// Header trusted proxies put the client address in (default:
+    /// `X-Forwarded-For`), e.g. `X-Real-IP`.
+    pub fn forwarded_header(mut self, name: impl Into<String>) -> Self {
+        self.access.forwarded_header = name.into().to_ascii_lowercase();
+        self
+    }
+
+    /// Reject request bodies larger than `bytes` with `413` (default: 1 MiB).
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Serve the liveness probe on `path` instead of `"/healthz"`.
+    ///
+    /// `GET` answers `200` once the webhook is registered and `503` before,
+    /// with a body like `{"webhook_registered":true}`. The probe is exempt
+    /// from the secret token and the IP allowlist.
+    pub fn health_path(mut self, path: impl Into<String>) -> Self {
+        self.health_path = Some(path.into());
+        self
+    }
+
+    /// Don't serve a liveness probe, e.g. when the application that mounts
+    /// [`WebhookServer::router`] has its own.
+    pub fn without_health_check(mut self) -> Self {
+        self.health_path = None;
+        self
+    }
+
+    /// Call `getWebhookInfo` every `interval` while [`WebhookServer::start`]
+    /// runs.
+    ///
+    /// The pending update count is logged at debug level and new delivery
+    /// errors (`last_error_message`) as warnings. If the registered URL or
+    /// allowed updates no longer match this server's settings, e.g. because
+    /// `setWebhook` was called elsewhere, the webhook is registered again.
+    pub fn monitor(mut self, interval: Duration) -> Self {
+        self.monitor_interval = Some(interval);
+        self
+    }
+
+    /// Call `f` with every [`WebhookInfo`] fetched by
+    /// [`WebhookServer::monitor`], e.g. to export `pending_update_count` as
+    /// a gauge.
+    pub fn on_webhook_info(mut self, f: impl Fn(&WebhookInfo) + Send + Sync + 'static) -> Self {
+        self.on_webhook_info = Some(Arc::new(f));
+        self
+    }
+
+    /// Answer updates `store` has already seen with `200` without passing
+    /// them on, so a redelivery doesn't run the handler twice. See
+    /// [`crate::dedup`].
+    pub fn dedup(mut self, store: impl DedupStore + 'static) -> Self {
+        self.dedup = Some(Arc::new(store));
+        self
+    }
+
+    /// Serve HTTPS instead of plain HTTP, for deployments without a reverse
+    /// proxy. Telegram only connects to ports 443, 80, 88 and 8443.
+    ///
+    /// ```rust,no_run
+    /// use tgbotrs::{Bot, UpdateHandler, WebhookServer, WebhookTls};
+    ///
+    /// # async fn example(bot: Bot, handler: UpdateHandler) -> Result<(), tgbotrs::BotError> {
+    /// let tls = WebhookTls::from_pem_files("cert.pem", "key.pem").await?.self_signed();
+    /// WebhookServer::new(bot, handler)
+    ///     .port(8443)
+    ///     .tls(tls)
+    ///     .start("https://203.0.113.7:8443")
+    ///     .await
+    /// # }
+    /// ```
+    #[cfg(feature = "webhook-tls")]
+    pub fn tls(mut self, tls: WebhookTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Use an existing [`StopHandle`], e.g. one shared with other services.
+    pub fn with_stop_handle(mut self, stop: StopHandle) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Handle that stops this server; see [`crate::shutdown`].
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Call `deleteWebhook` after the server stops, so Telegram stops sending
+    /// updates to an address nobody is listening on. Pending updates are kept
+    /// for whoever takes over, e.g. a [`Poller`](crate::Poller).
+    pub fn delete_webhook_on_shutdown(mut self) -> Self {
+        self.delete_on_shutdown = true;
+        self
+    }
+
     /// Register the webhook with Telegram and start the HTTP server.
     ///
     /// `webhook_url` is your public HTTPS base URL, e.g. `"https://mybot.example.com"`.
     /// The full webhook URL becomes `{webhook_url}{self.path}`.
     ///
-    /// Blocks until the server shuts down.
+    /// Blocks until stopped through [`WebhookServer::stop_handle`] or the
+    /// server fails.
     pub async fn start(self, webhook_url: &str) -> Result<(), BotError> {
-        let full_url = format!("{}{}", webhook_url.trim_end_matches('/'), self.path);
+        self.register(webhook_url).await?;
+
+        if let Some(interval) = self.monitor_interval {
+            let monitor = Monitor {
+                registration: self.registration(webhook_url),
+                interval,
+                on_info: self.on_webhook_info.clone(),
+            };
+            tokio::spawn(monitor.run(self.stop.clone()));
+        }
+
+        let bot = self.bot.clone();
+        let stop = self.stop.clone();
+        let delete_on_shutdown = self.delete_on_shutdown;
+        let addr = SocketAddr::new(self.host, self.port);
+        #[cfg(feature = "webhook-tls")]
+        let tls = self.tls.clone();
+        let app = self.router();
+
+        #[cfg(feature = "webhook-tls")]
+        let served = match tls {
+            Some(tls) => tls.serve(addr, app, stop).await,
+            None => serve_http(addr, app, stop).await,
+        };
+        #[cfg(not(feature = "webhook-tls"))]
+        let served = serve_http(addr, app, stop).await;
+        info!("webhook server stopped");
+
+        if delete_on_shutdown {
+            match bot.delete_webhook(None).await {
+                Ok(_) => info!("webhook deleted"),
+                Err(e) => warn!(error = %e, "failed to delete webhook on shutdown"),
+            }
+        }
+        served
+    }
+
+    /// Call `setWebhook` for `{webhook_url}{path}` with this server's
+    /// secret token, allowed updates and other settings.
+    ///
+    /// Only needed when mounting [`WebhookServer::router`] yourself;
+    /// [`WebhookServer::start`] does this for you.
+    pub async fn register(&self, webhook_url: &str) -> Result<(), BotError> {
+        self.registration(webhook_url).send().await
+    }
+
+    fn registration(&self, webhook_url: &str) -> Registration {
+        Registration {
+            bot: self.bot.clone(),
+            url: format!("{}{}", webhook_url.trim_end_matches('/'), self.path),
+            secret_token: self.secret_token.clone(),
+            allowed_updates: self.allowed_updates.clone(),
+            max_connections: self.max_connections,
+            drop_pending_updates: self.drop_pending_updates,
+            #[cfg(feature = "webhook-tls")]
+            certificate: self.tls.as_ref().and_then(WebhookTls::upload),
+            registered: Arc::clone(&self.registered),
+        }
+    }
 
-        let mut req = self.bot.set_webhook(full_url.clone());
+    /// An axum [`Router`] that accepts webhook POSTs on this server's path,
+    /// checks the source address and secret token and hands updates to the
+    /// handler or stream. It also serves the
+    /// [`health probe`](WebhookServer::health_path).
+    ///
+    /// Merge or nest it into your own application. It neither registers the
+    /// webhook nor binds a port; the host, port, stop handle and
+    /// delete-on-shutdown settings only apply to [`WebhookServer::start`].
+    pub fn router(self) -> Router {
+        let filter_ips = !self.access.allowed.is_empty();
+        let state = Arc::new(AppState {
+            bot: self.bot,
+            sink: self.sink,
+            secret_token: self.secret_token,
+            reply_deadline: self.reply_deadline,
+            access: self.access,
+            registered: self.registered,
+            dedup: self.dedup,
+        });
+        let mut app = Router::new().route(&self.path, post(handle_update));
+        // Route layers run before the body is read.
+        if filter_ips {
+            app = app.route_layer(middleware::from_fn_with_state(
+                Arc::clone(&state),
+                check_source_ip,
+            ));
+        }
+        app = app.layer(DefaultBodyLimit::max(self.max_body_size));
+        if let Some(path) = &self.health_path {
+            app = app.route(path, get(health));
+        }
+        app.with_state(state)
+    }
+}
+
+/// Everything needed to call `setWebhook`, detached from the server so the
+/// monitor can register again after [`WebhookServer::router`] consumed it.
+#[derive(Clone)]
+struct Registration {
+    bot: Bot,
+    url: String,
+    secret_token: Option<String>,
+    allowed_updates: Vec<String>,
+    max_connections: Option<i64>,
+    drop_pending_updates: bool,
+    #[cfg(feature = "webhook-tls")]
+    certificate: Option<crate::InputFile>,
+    registered: Arc<AtomicBool>,
+}
+
+impl Registration {
+    async fn send(&self) -> Result<(), BotError> {
+        let mut req = self.bot.set_webhook(self.url.clone());
         if let Some(ref token) = self.secret_token {
             req = req.secret_token(token.clone());
         }
@@ -154,56 +730,172 @@ impl WebhookServer {
         if self.drop_pending_updates {
             req = req.drop_pending_updates(true);
         }
+        #[cfg(feature = "webhook-tls")]
+        if let Some(cert) = self.certificate.clone() {
+            req = req.certificate(cert);
+        }
         req.await?;
-        info!(url = %full_url, "webhook registered");
+        self.registered.store(true, Ordering::Relaxed);
+        info!(url = %redact_token(&self.url), "webhook registered");
+        Ok(())
+    }
 
-        let state = Arc::new(AppState {
-            bot: self.bot,
-            handler: Arc::new(self.handler),
-            secret_token: self.secret_token,
-        });
+    /// Why `info` differs from what we registered, if it does.
+    fn drift(&self, info: &WebhookInfo) -> Option<&'static str> {
+        if info.url != self.url {
+            return Some("url");
+        }
+        // Without an explicit list Telegram keeps whatever was set before.
+        if !self.allowed_updates.is_empty() {
+            let mut want = self.allowed_updates.clone();
+            let mut have = info.allowed_updates.clone().unwrap_or_default();
+            want.sort();
+            have.sort();
+            if want != have {
+                return Some("allowed_updates");
+            }
+        }
+        None
+    }
+}
 
-        let app = Router::new()
-            .route(&self.path, post(handle_update))
-            .with_state(state);
+/// Background task behind [`WebhookServer::monitor`].
+struct Monitor {
+    registration: Registration,
+    interval: Duration,
+    on_info: Option<Arc<WebhookInfoFn>>,
+}
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-        info!(addr = %addr, "webhook server listening");
+impl Monitor {
+    async fn run(self, stop: StopHandle) {
+        let mut last_error_date = None;
+        loop {
+            tokio::select! {
+                _ = stop.stopped() => return,
+                _ = tokio::time::sleep(self.interval) => {}
+            }
+            let info = match self.registration.bot.get_webhook_info().await {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!(error = %e, "getWebhookInfo failed");
+                    continue;
+                }
+            };
+            if let Some(f) = &self.on_info {
+                f(&info);
+            }
+            debug!(
+                pending_update_count = info.pending_update_count,
+                "webhook info"
+            );
+            if info.last_error_date.is_some() && info.last_error_date != last_error_date {
+                warn!(
+                    error = info.last_error_message.as_deref().unwrap_or_default(),
+                    pending_update_count = info.pending_update_count,
+                    "Telegram reports webhook delivery errors"
+                );
+            }
+            last_error_date = info.last_error_date;
 
-        let listener = tokio::net::TcpListener::bind(addr)
-            .await
-            .map_err(|e| BotError::Other(format!("Failed to bind port {}: {}", self.port, e)))?;
+            if let Some(field) = self.registration.drift(&info) {
+                warn!(
+                    field,
+                    "webhook settings changed elsewhere - registering again"
+                );
+                self.registration.registered.store(false, Ordering::Relaxed);
+                // Don't throw away updates that queued up meanwhile.
+                let mut registration = self.registration.clone();
+                registration.drop_pending_updates = false;
+                if let Err(e) = registration.send().await {
+                    warn!(error = %e, "failed to register webhook again");
+                }
+            }
+        }
+    }
+}
 
-        axum::serve(listener, app)
-            .await
-            .map_err(|e| BotError::Other(format!("Webhook server error: {}", e)))?;
+async fn serve_http(addr: SocketAddr, app: Router, stop: StopHandle) -> Result<(), BotError> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| BotError::Other(format!("Failed to bind {}: {}", addr, e)))?;
+    info!(addr = %addr, "webhook server listening");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { stop.stopped().await })
+    .await
+    .map_err(|e| BotError::Other(format!("Webhook server error: {}", e)))
+}
 
-        Ok(())
+/// Reject requests from outside the allowed networks.
+async fn check_source_ip(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        warn!("peer address unknown - serve with connect info to filter by IP");
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let ip = state.access.client_ip(peer.ip(), req.headers());
+    if !state.access.allows(ip) {
+        warn!(%ip, "webhook request from disallowed address rejected");
+        return StatusCode::FORBIDDEN.into_response();
     }
+    next.run(req).await
+}
+
+async fn health(State(state): State<Arc<AppState>>) -> Response {
+    let registered = state.registered.load(Ordering::Relaxed);
+    let status = if registered {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::json!({ "webhook_registered": registered });
+    (status, Json(body)).into_response()
 }
 
 async fn handle_update(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
-    Json(update): Json<Update>,
-) -> StatusCode {
-    if let Some(ref expected) = state.secret_token {
-        let provided = headers
-            .get("x-telegram-bot-api-secret-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-
-        if provided != expected {
-            warn!("invalid secret token - webhook request rejected");
-            return StatusCode::FORBIDDEN;
+    body: Bytes,
+) -> Response {
+    if !secret_token_valid(&headers, state.secret_token.as_deref()) {
+        warn!("invalid secret token - webhook request rejected");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let update: Update = match serde_json::from_slice(&body) {
+        Ok(update) => update,
+        Err(e) => {
+            warn!(error = %e, "malformed webhook update rejected");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+    if let Some(store) = &state.dedup {
+        if !dedup::is_new(store.as_ref(), state.bot.me.id, update.update_id).await {
+            return StatusCode::OK.into_response();
         }
     }
 
+    let handler = match &state.sink {
+        Sink::Handler(handler) => Arc::clone(handler),
+        Sink::Reply(handler) => return reply(&state, Arc::clone(handler), update).await,
+        // Waiting for queue space is the backpressure.
+        Sink::Channel(tx) => {
+            let update_id = update.update_id;
+            if tx.send(update).await.is_ok() {
+                return StatusCode::OK.into_response();
+            }
+            // Telegram redelivers after a 503; let that attempt through.
+            if let Some(store) = &state.dedup {
+                dedup::forget(store.as_ref(), state.bot.me.id, update_id).await;
+            }
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    };
+
     // Spawn the handler so we return 200 immediately.
     // Telegram retries if we take too long or return non-2xx.
     // The outer spawn catches panics from the inner task via JoinError.
     let bot = state.bot.clone();
-    let handler = Arc::clone(&state.handler);
     tokio::spawn(async move {
         if let Err(join_err) = tokio::spawn(async move { (handler)(bot, update).await }).await {
             if join_err.is_panic() {
@@ -212,5 +904,230 @@ async fn handle_update(
         }
     });
 
-    StatusCode::OK
+    StatusCode::OK.into_response()
+}
+
+/// Run a reply handler, answering with its reply if it is ready in time.
+async fn reply(state: &AppState, handler: Arc<ReplyHandler>, update: Update) -> Response {
+    let (tx, mut rx) = oneshot::channel();
+    let bot = state.bot.clone();
+    tokio::spawn(async move {
+        let bot2 = bot.clone();
+        let reply = match tokio::spawn(async move { (handler)(bot2, update).await }).await {
+            Ok(reply) => reply,
+            Err(join_err) => {
+                if join_err.is_panic() {
+                    error!("handler panicked on webhook update - continuing");
+                }
+                return;
+            }
+        };
+        // The request already got its response; make the call ourselves.
+        if let Err(Some(late)) = tx.send(reply) {
+            let method = late.method.clone();
+            if let Err(e) = bot
+                .call_api::<serde_json::Value>(&method, late.params)
+                .await
+            {
+                warn!(method, error = %e, "late webhook reply failed");
+            }
+        }
+    });
+
+    let reply = match tokio::time::timeout(state.reply_deadline, &mut rx).await {
+        Ok(reply) => reply.ok().flatten(),
+        Err(_) => {
+            // A reply may have arrived just as the deadline passed.
+            rx.close();
+            rx.try_recv().ok().flatten()
+        }
+    };
+    match reply {
+        Some(reply) => Json(reply.body()).into_response(),
+        None => StatusCode::OK.into_response(),
+    }
+}
+
+/// Certificate and key for [`WebhookServer::tls`] (`webhook-tls` feature).
+#[cfg(feature = "webhook-tls")]
+#[derive(Clone)]
+pub struct WebhookTls {
+    cert_pem: Vec<u8>,
+    key_pem: Vec<u8>,
+    self_signed: bool,
+}
+
+#[cfg(feature = "webhook-tls")]
+impl std::fmt::Debug for WebhookTls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookTls")
+            .field("self_signed", &self.self_signed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "webhook-tls")]
+impl WebhookTls {
+    /// PEM-encoded certificate chain and private key.
+    pub fn from_pem(cert_pem: impl Into<Vec<u8>>, key_pem: impl Into<Vec<u8>>) -> Self {
+        Self {
+            cert_pem: cert_pem.into(),
+            key_pem: key_pem.into(),
+            self_signed: false,
+        }
+    }
+
+    /// Read the PEM certificate chain and private key from files.
+    pub async fn from_pem_files(
+        cert_path: impl AsRef<std::path::Path>,
+        key_path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, BotError> {
+        let cert_pem = tokio::fs::read(cert_path).await?;
+        let key_pem = tokio::fs::read(key_path).await?;
+        Ok(Self::from_pem(cert_pem, key_pem))
+    }
+
+    /// The certificate is self-signed: upload it with `setWebhook` so
+    /// Telegram trusts it.
+    pub fn self_signed(mut self) -> Self {
+        self.self_signed = true;
+        self
+    }
+
+    /// The public certificate to pass to `setWebhook`, if it must be uploaded.
+    fn upload(&self) -> Option<crate::InputFile> {
+        self.self_signed
+            .then(|| crate::InputFile::memory("certificate.pem", self.cert_pem.clone()))
+    }
+
+    fn server_config(&self) -> Result<rustls::ServerConfig, BotError> {
+        use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+
+        let invalid = |what: &str, e: &dyn std::fmt::Display| {
+            BotError::Other(format!("invalid webhook TLS {what}: {e}"))
+        };
+        let certs = CertificateDer::pem_slice_iter(&self.cert_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid("certificate", &e))?;
+        let key = PrivateKeyDer::from_pem_slice(&self.key_pem).map_err(|e| invalid("key", &e))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| invalid("configuration", &e))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid("certificate", &e))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    async fn serve(&self, addr: SocketAddr, app: Router, stop: StopHandle) -> Result<(), BotError> {
+        use axum_server::tls_rustls::RustlsConfig;
+
+        let config = RustlsConfig::from_config(Arc::new(self.server_config()?));
+        let handle = axum_server::Handle::new();
+        let shutdown = handle.clone();
+        tokio::spawn(async move {
+            stop.stopped().await;
+            shutdown.graceful_shutdown(None);
+        });
+        info!(addr = %addr, "webhook server listening (TLS)");
+        axum_server::bind_rustls(addr, config)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .map_err(|e| BotError::Other(format!("Webhook server error: {}", e)))
+    }
+}
+
+/// Whether `headers` carry the expected `X-Telegram-Bot-Api-Secret-Token`.
+/// Always true when no secret is configured.
+pub(crate) fn secret_token_valid(headers: &HeaderMap, expected: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+    let provided = headers
+        .get("x-telegram-bot-api-secret-token")
+        .map(|v| v.as_bytes())
+        .unwrap_or_default();
+    constant_time_eq(provided, expected.as_bytes())
+}
+
+/// Compare without stopping at the first differing byte, so response timing
+/// doesn't reveal how much of a guessed secret was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_token_check() {
+        let mut headers = HeaderMap::new();
+        assert!(secret_token_valid(&headers, None));
+        assert!(!secret_token_valid(&headers, Some("abc")));
+        headers.insert("x-telegram-bot-api-secret-token", "abd".parse().unwrap());
+        assert!(!secret_token_valid(&headers, Some("abc")));
+        assert!(!secret_token_valid(&headers, Some("abcd")));
+        headers.insert("x-telegram-bot-api-secret-token", "abc".parse().unwrap());
+        assert!(secret_token_valid(&headers, Some("abc")));
+    }
+
+    #[test]
+    fn ip_net_contains() {
+        let net = IpNet::new([149, 154, 160, 0].into(), 20);
+        assert!(net.contains([149, 154, 167, 99].into()));
+        assert!(net.contains("::ffff:149.154.175.255".parse().unwrap()));
+        assert!(!net.contains([149, 154, 176, 0].into()));
+        assert!(!net.contains("2001:db8::1".parse().unwrap()));
+        assert!(IpNet::new([0, 0, 0, 0].into(), 0).contains([8, 8, 8, 8].into()));
+        let v6 = IpNet::new("2001:db8::".parse().unwrap(), 32);
+        assert!(v6.contains("2001:db8:ffff::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ip_net_accepts_ipv4_mapped_networks() {
+        let net = IpNet::new("::ffff:10.0.0.0".parse().unwrap(), 104);
+        assert_eq!(net, IpNet::new([10, 0, 0, 0].into(), 8));
+        assert!(net.contains([10, 1, 2, 3].into()));
+        assert!(net.contains("::ffff:10.9.9.9".parse().unwrap()));
+        assert!(!net.contains([11, 0, 0, 1].into()));
+        // Out-of-range prefixes are clamped rather than overflowing.
+        assert_eq!(
+            IpNet::new("::ffff:10.0.0.1".parse().unwrap(), 200).prefix,
+            32
+        );
+        assert_eq!(IpNet::new("::ffff:10.0.0.1".parse().unwrap(), 64).prefix, 0);
+        assert_eq!(IpNet::new([10, 0, 0, 1].into(), 40).prefix, 32);
+    }
+
+    #[test]
+    fn client_ip_only_trusts_proxies() {
+        let access = Access {
+            allowed: vec![],
+            trusted_proxies: vec![IpNet::new([10, 0, 0, 0].into(), 8)],
+            forwarded_header: "x-forwarded-for".into(),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "1.2.3.4, 149.154.167.1, 10.0.0.2".parse().unwrap(),
+        );
+        // Untrusted peers can't spoof their address.
+        let peer: IpAddr = [203, 0, 113, 5].into();
+        assert_eq!(access.client_ip(peer, &headers), peer);
+        // Behind two proxies the first untrusted hop from the right wins.
+        assert_eq!(
+            access.client_ip([10, 0, 0, 1].into(), &headers),
+            IpAddr::from([149, 154, 167, 1])
+        );
+        // A proxy without the header is the client itself.
+        assert_eq!(
+            access.client_ip([10, 0, 0, 1].into(), &HeaderMap::new()),
+            IpAddr::from([10, 0, 0, 1])
+        );
+    }
 }